@@ -0,0 +1,339 @@
+use crate::TshError;
+use crate::candidate;
+use crate::fuzzy;
+use crate::picker::{ACTION_HELP, Action, Selection};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Stderr, Write};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
+
+const HEADER_LINES: u16 = 2;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const RECEIVE_BATCH: usize = 10_000;
+
+struct Screen {
+    out: Stderr,
+}
+
+impl Screen {
+    fn enter() -> io::Result<Screen> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stderr();
+        if let Err(e) = execute!(out, EnterAlternateScreen, Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e);
+        }
+        Ok(Screen { out })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Candidate {
+    index: usize,
+    score: i64,
+    positions: Vec<usize>,
+}
+
+struct Finder {
+    items: Vec<String>,
+    scanning: bool,
+    query: String,
+    matches: Vec<Candidate>,
+    marked: Vec<usize>,
+    cursor: usize,
+    offset: usize,
+}
+
+enum Step {
+    Continue,
+    Accept(Action),
+    Abort,
+}
+
+pub fn select(lines: Receiver<String>) -> Result<Option<Selection>, TshError> {
+    let mut finder = Finder {
+        items: Vec::new(),
+        scanning: true,
+        query: String::new(),
+        matches: Vec::new(),
+        marked: Vec::new(),
+        cursor: 0,
+        offset: 0,
+    };
+
+    let mut screen = Screen::enter()?;
+    loop {
+        finder.receive(&lines);
+        let (width, height) = terminal::size()?;
+        let rows = height.saturating_sub(HEADER_LINES).max(1) as usize;
+        finder.scroll(rows);
+        finder.draw(&mut screen.out, width as usize, rows)?;
+
+        // While candidates are still arriving, wake up regularly to show them.
+        if finder.scanning && !event::poll(POLL_INTERVAL)? {
+            continue;
+        }
+        let step = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => finder.handle(key, rows),
+            _ => Step::Continue,
+        };
+        match step {
+            Step::Continue => {}
+            Step::Abort => return Ok(None),
+            Step::Accept(action) => {
+                let chosen = if finder.marked.is_empty() {
+                    finder
+                        .matches
+                        .get(finder.cursor)
+                        .map(|candidate| candidate.index)
+                        .into_iter()
+                        .collect()
+                } else {
+                    finder.marked
+                };
+                if chosen.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some((
+                    action,
+                    chosen
+                        .into_iter()
+                        .map(|i| finder.items[i].clone())
+                        .collect(),
+                )));
+            }
+        }
+    }
+}
+
+impl Finder {
+    // Bounded so that a fast walker cannot starve keyboard input.
+    fn receive(&mut self, lines: &Receiver<String>) {
+        for _ in 0..RECEIVE_BATCH {
+            match lines.try_recv() {
+                Ok(line) => {
+                    self.items.push(line);
+                    let Some(candidate) = self.score(self.items.len() - 1) else {
+                        continue;
+                    };
+                    let pos = self
+                        .matches
+                        .partition_point(|other| other.score >= candidate.score);
+                    if pos <= self.cursor && !self.matches.is_empty() {
+                        self.cursor += 1;
+                    }
+                    self.matches.insert(pos, candidate);
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    self.scanning = false;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn score(&self, index: usize) -> Option<Candidate> {
+        if self.query.trim().is_empty() {
+            return Some(Candidate {
+                index,
+                score: 0,
+                positions: Vec::new(),
+            });
+        }
+        let found = fuzzy::find(candidate::display_text(&self.items[index]), &self.query)?;
+        Some(Candidate {
+            index,
+            score: found.score,
+            positions: found.positions,
+        })
+    }
+
+    fn filter(&mut self) {
+        let mut matches: Vec<Candidate> = (0..self.items.len())
+            .filter_map(|index| self.score(index))
+            .collect();
+        matches.sort_by_key(|candidate| std::cmp::Reverse(candidate.score));
+        self.matches = matches;
+        self.cursor = 0;
+        self.offset = 0;
+    }
+
+    fn handle(&mut self, key: KeyEvent, rows: usize) -> Step {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let last = self.matches.len().saturating_sub(1);
+
+        match key.code {
+            KeyCode::Esc => return Step::Abort,
+            KeyCode::Char('c' | 'g' | 'q') if ctrl => return Step::Abort,
+            KeyCode::Enter => return Step::Accept(Action::Open),
+            KeyCode::Char(c @ ('x' | 'r' | 'w' | 'o')) if ctrl => {
+                return Step::Accept(Action::from_key(&format!("ctrl-{}", c)));
+            }
+            KeyCode::Tab => {
+                self.toggle_mark();
+                self.cursor = (self.cursor + 1).min(last);
+            }
+            KeyCode::BackTab => {
+                self.toggle_mark();
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Char('p' | 'k') if ctrl => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => self.cursor = (self.cursor + 1).min(last),
+            KeyCode::Char('n' | 'j') if ctrl => self.cursor = (self.cursor + 1).min(last),
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(rows),
+            KeyCode::PageDown => self.cursor = (self.cursor + rows).min(last),
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.filter();
+            }
+            KeyCode::Backspace if alt => {
+                let kept = self.query.trim_end().rfind(' ').map_or(0, |i| i + 1);
+                self.query.truncate(kept);
+                self.filter();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.filter();
+            }
+            KeyCode::Char('h') if ctrl => {
+                self.query.pop();
+                self.filter();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.filter();
+            }
+            _ => {}
+        }
+
+        Step::Continue
+    }
+
+    fn toggle_mark(&mut self) {
+        let Some(candidate) = self.matches.get(self.cursor) else {
+            return;
+        };
+        match self.marked.iter().position(|&i| i == candidate.index) {
+            Some(pos) => {
+                self.marked.remove(pos);
+            }
+            None => self.marked.push(candidate.index),
+        }
+    }
+
+    fn scroll(&mut self, rows: usize) {
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + rows {
+            self.offset = self.cursor + 1 - rows;
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write, width: usize, rows: usize) -> io::Result<()> {
+        queue!(
+            out,
+            MoveTo(0, 0),
+            Clear(ClearType::CurrentLine),
+            SetForegroundColor(Color::Blue),
+            Print("> "),
+            SetForegroundColor(Color::Reset),
+            Print(truncate(&self.query, width.saturating_sub(2))),
+            MoveTo(0, 1),
+            Clear(ClearType::CurrentLine),
+            SetForegroundColor(Color::DarkGrey),
+            Print(format!(
+                "  {}/{}{}{}  {}",
+                self.matches.len(),
+                self.items.len(),
+                if self.scanning { " (scanning)" } else { "" },
+                match self.marked.len() {
+                    0 => String::new(),
+                    n => format!(" ({} marked)", n),
+                },
+                ACTION_HELP
+            )),
+            SetForegroundColor(Color::Reset),
+        )?;
+
+        let visible = self.matches.iter().skip(self.offset).take(rows);
+        let shown = visible.len();
+        for (row, candidate) in visible.enumerate() {
+            let selected = self.offset + row == self.cursor;
+            queue!(
+                out,
+                MoveTo(0, row as u16 + HEADER_LINES),
+                Clear(ClearType::CurrentLine)
+            )?;
+            let marker = if self.marked.contains(&candidate.index) {
+                '+'
+            } else {
+                ' '
+            };
+            if selected {
+                queue!(
+                    out,
+                    SetForegroundColor(Color::Red),
+                    Print('>'),
+                    SetForegroundColor(Color::Magenta),
+                    Print(marker),
+                    SetForegroundColor(Color::Reset),
+                    SetAttribute(Attribute::Bold),
+                )?;
+            } else {
+                queue!(
+                    out,
+                    Print(' '),
+                    SetForegroundColor(Color::Magenta),
+                    Print(marker),
+                    SetForegroundColor(Color::Reset),
+                )?;
+            }
+
+            let mut positions = candidate.positions.iter().peekable();
+            for (i, c) in candidate::display_text(&self.items[candidate.index])
+                .chars()
+                .take(width.saturating_sub(2))
+                .enumerate()
+            {
+                if positions.next_if_eq(&&i).is_some() {
+                    queue!(
+                        out,
+                        SetForegroundColor(Color::Green),
+                        Print(c),
+                        SetForegroundColor(Color::Reset),
+                    )?;
+                } else {
+                    queue!(out, Print(c))?;
+                }
+            }
+            queue!(out, SetAttribute(Attribute::Reset))?;
+        }
+
+        queue!(
+            out,
+            MoveTo(0, shown as u16 + HEADER_LINES),
+            Clear(ClearType::FromCursorDown)
+        )?;
+
+        let cursor = 2 + self.query.chars().count().min(width.saturating_sub(3));
+        queue!(out, MoveTo(cursor as u16, 0), Show)?;
+        out.flush()
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
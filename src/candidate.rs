@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+const SESSION_PREFIX: &str = "tmux:";
+
+// Picker lines carry the display text and the key separated by a tab; only the
+// display text is shown, the key identifies the candidate on selection.
+pub const FIELD_SEPARATOR: char = '\t';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Candidate {
+    Session(String),
+    Directory(PathBuf),
+}
+
+impl Candidate {
+    pub fn key(&self) -> String {
+        match self {
+            Candidate::Session(name) => format!("{}{}", SESSION_PREFIX, name),
+            Candidate::Directory(dir) => dir.display().to_string(),
+        }
+    }
+
+    pub fn parse(line: &str) -> Candidate {
+        let key = line
+            .rsplit_once(FIELD_SEPARATOR)
+            .map_or(line, |(_, key)| key);
+        match key.strip_prefix(SESSION_PREFIX) {
+            Some(name) => Candidate::Session(name.to_string()),
+            None => Candidate::Directory(PathBuf::from(key)),
+        }
+    }
+}
+
+pub fn display_text(line: &str) -> &str {
+    line.split_once(FIELD_SEPARATOR)
+        .map_or(line, |(display, _)| display)
+}
@@ -0,0 +1,278 @@
+use crate::import;
+use crate::picker::Backend;
+use crate::sort::SortOrder;
+use clap::error::ErrorKind;
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+pub fn build() -> Command {
+    Command::new("tsh")
+        .version("0.1.0")
+        .about("Tmux Session Handler - Select directories with fzf and create tmux sessions")
+        .subcommand_precedence_over_arg(true)
+        .arg(
+            Arg::new("directory")
+                .action(ArgAction::Append)
+                .help("Directory names, globs or re:REGEX patterns to look up in the search roots (falls back to fuzzy matching, implies --select-1 and --exit-0)"),
+        )
+        .arg(
+            Arg::new("dir")
+                .short('d')
+                .long("dir")
+                .value_name("PATH")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .global(true)
+                .help("Add a directory to search in (repeatable, replaces configured roots)")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .value_name("FILE")
+                .num_args(1)
+                .global(true)
+                .help("Read configuration from FILE instead of ~/.config/tsh/config.toml")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("min_depth")
+                .long("min-depth")
+                .value_name("N")
+                .num_args(1)
+                .global(true)
+                .help("Only list directories at least N levels below the search root")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("max_depth")
+                .long("max-depth")
+                .value_name("N")
+                .num_args(1)
+                .global(true)
+                .help("Do not descend more than N levels below the search root")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("projects")
+                .long("projects")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Only list project roots and do not descend into them"),
+        )
+        .arg(
+            Arg::new("marker")
+                .long("marker")
+                .value_name("NAME")
+                .global(true)
+                .action(ArgAction::Append)
+                .help("File or directory marking a project root (implies --projects)"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .value_name("GLOB")
+                .global(true)
+                .action(ArgAction::Append)
+                .help("Skip directories matching GLOB (name, or full path if it contains '/')"),
+        )
+        .arg(
+            Arg::new("exclude_regex")
+                .long("exclude-regex")
+                .value_name("REGEX")
+                .global(true)
+                .action(ArgAction::Append)
+                .help("Skip directories whose full path matches REGEX"),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .value_name("GLOB")
+                .global(true)
+                .action(ArgAction::Append)
+                .help("Only list directories matching GLOB"),
+        )
+        .arg(
+            Arg::new("include_regex")
+                .long("include-regex")
+                .value_name("REGEX")
+                .global(true)
+                .action(ArgAction::Append)
+                .help("Only list directories whose full path matches REGEX"),
+        )
+        .arg(
+            Arg::new("no_default_excludes")
+                .long("no-default-excludes")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Drop exclude rules inherited from the defaults and the config file"),
+        )
+        .arg(
+            Arg::new("show_ignored")
+                .long("show-ignored")
+                .visible_alias("no-ignore")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("List directories excluded by .gitignore, .ignore and .tshignore files"),
+        )
+        .arg(
+            Arg::new("select_1")
+                .short('1')
+                .long("select-1")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Open the session directly when only one directory matches"),
+        )
+        .arg(
+            Arg::new("exit_0")
+                .short('0')
+                .long("exit-0")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Exit with status 3 instead of opening the picker when nothing matches"),
+        )
+        .arg(
+            Arg::new("picker")
+                .long("picker")
+                .value_name("BACKEND")
+                .num_args(1)
+                .global(true)
+                .help("Picker used to choose a directory [default: auto]")
+                .value_parser(value_parser!(Backend)),
+        )
+        .arg(
+            Arg::new("no_sessions")
+                .long("no-sessions")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Do not list running tmux sessions in the picker"),
+        )
+        .arg(
+            Arg::new("template")
+                .short('t')
+                .long("template")
+                .value_name("NAME")
+                .num_args(1)
+                .global(true)
+                .help("Lay out new sessions with the named template from the config file"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Report the detected project type and the layout used for new sessions"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("ORDER")
+                .num_args(1)
+                .global(true)
+                .help("Order in which candidates are passed to fzf [default: frecency]")
+                .value_parser(value_parser!(SortOrder)),
+        )
+        .subcommand(
+            Command::new("index")
+                .about("Manage the cached directory index")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("rebuild").about("Rebuild the directory index from scratch"),
+                )
+                .subcommand(Command::new("status").about("Show the state of the directory index")),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Seed the visit history from zoxide, z, autojump or fasd")
+                .arg(
+                    Arg::new("source")
+                        .action(ArgAction::Append)
+                        .help("Tools to import from (defaults to all of them)")
+                        .value_parser(value_parser!(import::Source)),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .value_name("PATH")
+                        .num_args(1)
+                        .help("Read the database from PATH instead of its default location")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Inspect the layered configuration")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("show")
+                        .about("Print the effective configuration and where each value came from"),
+                ),
+        )
+        .subcommand(
+            Command::new("preview")
+                .about("Print the preview shown in the picker for a directory or session")
+                .arg(Arg::new("candidate").required(true)),
+        )
+        .subcommand(
+            Command::new("daemon")
+                .about("Watch the search root with inotify and keep the directory index current"),
+        )
+}
+
+// Flags are global, so they may come before a subcommand; its name still wins
+// over the directory patterns, which are rejected next to a subcommand rather
+// than silently ignored.
+pub fn parse<I, T>(args: I) -> Result<ArgMatches, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let mut command = build();
+    let matches = command.try_get_matches_from_mut(args)?;
+    if matches.subcommand().is_some() && matches.contains_id("directory") {
+        return Err(command.error(
+            ErrorKind::ArgumentConflict,
+            "directory patterns cannot be combined with a subcommand",
+        ));
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_before_a_subcommand_keep_the_subcommand() {
+        let matches = parse(["tsh", "--config", "/tmp/c.toml", "config", "show"]).unwrap();
+        let Some(("config", config)) = matches.subcommand() else {
+            panic!("expected the config subcommand");
+        };
+        assert_eq!(config.subcommand_name(), Some("show"));
+        assert_eq!(
+            config.get_one::<PathBuf>("config"),
+            Some(&PathBuf::from("/tmp/c.toml"))
+        );
+        assert!(!matches.contains_id("directory"));
+
+        let matches = parse(["tsh", "-d", "/tmp/work", "index", "rebuild"]).unwrap();
+        assert_eq!(matches.subcommand_name(), Some("index"));
+    }
+
+    #[test]
+    fn directories_next_to_a_subcommand_are_rejected() {
+        let e = parse(["tsh", "work", "config", "show"]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn directories_alone_are_lookups() {
+        let matches = parse(["tsh", "-d", "/tmp", "work", "api"]).unwrap();
+        assert_eq!(matches.subcommand_name(), None);
+        let directories: Vec<&String> = matches.get_many("directory").unwrap().collect();
+        assert_eq!(directories, ["work", "api"]);
+    }
+}
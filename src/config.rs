@@ -0,0 +1,882 @@
+use crate::TshError;
+use crate::display::PathStyle;
+use crate::filter::{self, PatternSet};
+use crate::layout::Template;
+use crate::picker::Backend;
+use crate::project::ProjectType;
+use crate::sort::SortOrder;
+use crate::walker::WalkOptions;
+use crate::xdg;
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+pub const DEFAULT_PROJECT_MARKERS: &[&str] = &[
+    ".git",
+    ".hg",
+    ".jj",
+    "Cargo.toml",
+    "package.json",
+    "go.mod",
+    "flake.nix",
+    "pyproject.toml",
+];
+
+const SHOW_ALIGN_WIDTH: usize = 48;
+
+const ENV_VARS: &[(&str, &str, EnvKind)] = &[
+    ("TSH_ROOTS", "roots", EnvKind::Paths),
+    ("TSH_MIN_DEPTH", "min_depth", EnvKind::Integer),
+    ("TSH_MAX_DEPTH", "max_depth", EnvKind::Integer),
+    ("TSH_EXCLUDE", "exclude", EnvKind::List),
+    ("TSH_EXCLUDE_REGEX", "exclude_regex", EnvKind::List),
+    ("TSH_INCLUDE", "include", EnvKind::List),
+    ("TSH_INCLUDE_REGEX", "include_regex", EnvKind::List),
+    ("TSH_PROJECTS", "projects", EnvKind::Bool),
+    ("TSH_PROJECT_MARKERS", "project_markers", EnvKind::List),
+    ("TSH_IGNORE_FILES", "ignore_files", EnvKind::Bool),
+    ("TSH_SORT", "sort", EnvKind::String),
+    ("TSH_VERBOSE", "verbose", EnvKind::Bool),
+    ("TSH_PICKER", "picker.backend", EnvKind::String),
+    ("TSH_PICKER_ARGS", "picker.args", EnvKind::Words),
+    ("TSH_SELECT_1", "picker.select_1", EnvKind::Bool),
+    ("TSH_EXIT_0", "picker.exit_0", EnvKind::Bool),
+    ("TSH_PREVIEW", "picker.preview", EnvKind::Bool),
+    ("TSH_SESSIONS", "picker.sessions", EnvKind::Bool),
+    ("TSH_PATHS", "picker.paths", EnvKind::String),
+    ("TSH_ICONS", "picker.icons", EnvKind::Bool),
+    ("TSH_GIT_STATUS", "picker.git_status", EnvKind::Bool),
+    (
+        "TSH_GIT_BUDGET_MS",
+        "picker.git_budget_ms",
+        EnvKind::Integer,
+    ),
+    ("TSH_SESSION_NAMING", "session.naming", EnvKind::String),
+    (
+        "TSH_SESSION_NAME_TEMPLATE",
+        "session.name_template",
+        EnvKind::String,
+    ),
+    ("TSH_TEMPLATE", "session.template", EnvKind::String),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum EnvKind {
+    String,
+    Integer,
+    Bool,
+    List,
+    Paths,
+    Words,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    Env(String),
+    Cli(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Env(var) => write!(f, "env {}", var),
+            Origin::Cli(flag) => write!(f, "{}", flag),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub roots: Vec<RootEntry>,
+    pub min_depth: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    pub exclude: Vec<String>,
+    pub exclude_regex: Vec<String>,
+    pub include: Vec<String>,
+    pub include_regex: Vec<String>,
+    pub projects: bool,
+    pub project_markers: Vec<String>,
+    pub ignore_files: bool,
+    pub sort: SortOrder,
+    pub verbose: bool,
+    pub picker: PickerConfig,
+    pub session: SessionConfig,
+    pub templates: BTreeMap<String, Template>,
+    pub project_types: Vec<ProjectType>,
+    #[serde(skip)]
+    origins: BTreeMap<String, Origin>,
+    #[serde(skip)]
+    effective: Table,
+    #[serde(skip)]
+    location: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RootEntry {
+    Path(String),
+    Table(RootConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RootConfig {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_regex: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include_regex: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchRoot {
+    pub path: PathBuf,
+    pub label: String,
+    pub options: WalkOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PickerConfig {
+    pub backend: Backend,
+    pub args: Vec<String>,
+    pub select_1: bool,
+    pub exit_0: bool,
+    pub preview: bool,
+    pub sessions: bool,
+    pub paths: PathStyle,
+    pub icons: bool,
+    pub git_status: bool,
+    pub git_budget_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    pub naming: Naming,
+    pub name_template: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Naming {
+    Basename,
+    Parent,
+    Path,
+    Template,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            roots: vec![RootEntry::Path("~".to_string())],
+            min_depth: 0,
+            max_depth: None,
+            exclude: filter::DEFAULT_EXCLUDES
+                .iter()
+                .map(|g| g.to_string())
+                .collect(),
+            exclude_regex: Vec::new(),
+            include: Vec::new(),
+            include_regex: Vec::new(),
+            projects: false,
+            project_markers: DEFAULT_PROJECT_MARKERS
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            ignore_files: true,
+            sort: SortOrder::Frecency,
+            verbose: false,
+            picker: PickerConfig::default(),
+            session: SessionConfig::default(),
+            templates: BTreeMap::new(),
+            project_types: ProjectType::defaults(),
+            origins: BTreeMap::new(),
+            effective: Table::new(),
+            location: None,
+        }
+    }
+}
+
+impl Default for PickerConfig {
+    fn default() -> Self {
+        PickerConfig {
+            backend: Backend::Auto,
+            args: Vec::new(),
+            select_1: false,
+            exit_0: false,
+            preview: true,
+            sessions: true,
+            paths: PathStyle::Home,
+            icons: false,
+            git_status: true,
+            git_budget_ms: 250,
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            naming: Naming::Basename,
+            name_template: "{basename}".to_string(),
+            template: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn location(matches: &ArgMatches) -> Option<PathBuf> {
+        matches
+            .get_one::<PathBuf>("config")
+            .cloned()
+            .or_else(|| env::var_os("TSH_CONFIG").map(PathBuf::from))
+            .or_else(|| xdg::config_home().map(|dir| dir.join("tsh").join("config.toml")))
+    }
+
+    pub fn load(matches: &ArgMatches) -> Result<Config, TshError> {
+        let mut merged = Table::new();
+        let mut origins = BTreeMap::new();
+
+        let defaults = match Value::try_from(Config::default()).map_err(invalid)? {
+            Value::Table(table) => table,
+            _ => Table::new(),
+        };
+        apply(&mut merged, defaults, &Origin::Default, &mut origins, "");
+
+        let location = Config::location(matches);
+        if let Some(path) = location.clone() {
+            match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    let table: Table = toml::from_str(&contents).map_err(|e| {
+                        TshError::InvalidConfig(format!("{}: {}", path.display(), e))
+                    })?;
+                    apply(&mut merged, table, &Origin::File(path), &mut origins, "");
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        for (var, key, kind) in ENV_VARS {
+            let Ok(raw) = env::var(var) else {
+                continue;
+            };
+            let value = env_value(&raw, *kind)
+                .ok_or_else(|| TshError::InvalidConfig(format!("{}={}", var, raw)))?;
+            let mut layer = Table::new();
+            set_dotted(&mut layer, key, value);
+            apply(
+                &mut merged,
+                layer,
+                &Origin::Env(var.to_string()),
+                &mut origins,
+                "",
+            );
+        }
+
+        for (flag, layer) in cli_layers(matches, &merged) {
+            apply(&mut merged, layer, &Origin::Cli(flag), &mut origins, "");
+        }
+
+        let mut config: Config = Value::Table(merged.clone()).try_into().map_err(invalid)?;
+        config.origins = origins;
+        config.effective = merged;
+        config.location = location;
+
+        Ok(config)
+    }
+
+    pub fn origin(&self, key: &str) -> &Origin {
+        self.origins.get(key).unwrap_or(&Origin::Default)
+    }
+
+    // The config file plus the command-line settings as the TSH_* variables
+    // that reproduce them, so that a child tsh (the picker preview) sees the
+    // same configuration; settings from the environment are inherited anyway.
+    pub fn child_env(&self) -> Vec<(&'static str, OsString)> {
+        let mut vars = Vec::new();
+        if let Some(location) = &self.location {
+            vars.push(("TSH_CONFIG", location.clone().into_os_string()));
+        }
+        for (var, key, kind) in ENV_VARS {
+            if !matches!(self.origin(key), Origin::Cli(_)) {
+                continue;
+            }
+            if let Some(raw) = get_dotted(&self.effective, key).and_then(|v| env_raw(v, *kind)) {
+                vars.push((*var, raw.into()));
+            }
+        }
+        vars
+    }
+
+    pub fn search_roots(&self) -> Result<Vec<SearchRoot>, TshError> {
+        let mut roots: Vec<SearchRoot> = Vec::new();
+
+        for entry in &self.roots {
+            let root = match entry {
+                RootEntry::Path(path) => RootConfig {
+                    path: path.clone(),
+                    label: None,
+                    min_depth: None,
+                    max_depth: None,
+                    exclude: Vec::new(),
+                    exclude_regex: Vec::new(),
+                    include: Vec::new(),
+                    include_regex: Vec::new(),
+                },
+                RootEntry::Table(root) => root.clone(),
+            };
+
+            let path = match fs::canonicalize(filter::expand_home(&root.path)) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("Skipping search root {}: {}", root.path, e);
+                    continue;
+                }
+            };
+            if roots.iter().any(|known| known.path == path) {
+                continue;
+            }
+
+            let join = |global: &[String], local: &[String]| -> Vec<String> {
+                global.iter().chain(local).cloned().collect()
+            };
+            let mut options = self.walk_options()?;
+            options.min_depth = root.min_depth.unwrap_or(self.min_depth);
+            options.max_depth = root.max_depth.or(self.max_depth);
+            options.exclude = PatternSet::new(
+                &join(&self.exclude, &root.exclude),
+                &join(&self.exclude_regex, &root.exclude_regex),
+            )?;
+            options.include = PatternSet::new(
+                &join(&self.include, &root.include),
+                &join(&self.include_regex, &root.include_regex),
+            )?;
+
+            let label = root.label.unwrap_or_else(|| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.display().to_string())
+            });
+
+            roots.push(SearchRoot {
+                path,
+                label,
+                options,
+            });
+        }
+
+        if roots.is_empty() {
+            return Err(TshError::InvalidConfig(
+                "none of the configured search roots exist".to_string(),
+            ));
+        }
+
+        Ok(roots)
+    }
+
+    pub fn template(&self) -> Result<Option<(&str, &Template)>, TshError> {
+        let Some(name) = &self.session.template else {
+            return Ok(None);
+        };
+        self.templates
+            .get_key_value(name)
+            .map(|(name, template)| Some((name.as_str(), template)))
+            .ok_or_else(|| TshError::InvalidConfig(format!("unknown template '{}'", name)))
+    }
+
+    pub fn walk_options(&self) -> Result<WalkOptions, TshError> {
+        Ok(WalkOptions {
+            min_depth: self.min_depth,
+            max_depth: self.max_depth,
+            exclude: PatternSet::new(&self.exclude, &self.exclude_regex)?,
+            include: PatternSet::new(&self.include, &self.include_regex)?,
+            project_markers: if self.projects {
+                self.project_markers.clone()
+            } else {
+                Vec::new()
+            },
+            ignore_files: self.ignore_files,
+            ..WalkOptions::default()
+        })
+    }
+
+    pub fn show(&self) -> String {
+        let mut lines = Vec::new();
+        flatten(&self.effective, "", &mut lines);
+
+        let width = lines
+            .iter()
+            .map(|(key, value)| key.len() + value.len() + 3)
+            .max()
+            .unwrap_or(0)
+            .min(SHOW_ALIGN_WIDTH);
+
+        lines
+            .iter()
+            .map(|(key, value)| {
+                let assignment = format!("{} = {}", key, value);
+                format!("{:<width$}  # {}\n", assignment, self.origin(key))
+            })
+            .collect()
+    }
+}
+
+fn apply(
+    merged: &mut Table,
+    layer: Table,
+    origin: &Origin,
+    origins: &mut BTreeMap<String, Origin>,
+    prefix: &str,
+) {
+    for (key, value) in layer {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        match (merged.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(table)) => {
+                apply(existing, table, origin, origins, &path);
+            }
+            (_, value) => {
+                let mut leaves = Vec::new();
+                match &value {
+                    Value::Table(table) => flatten(table, &path, &mut leaves),
+                    _ => leaves.push((path, String::new())),
+                }
+                for (leaf, _) in leaves {
+                    origins.insert(leaf, origin.clone());
+                }
+                merged.insert(key, value);
+            }
+        }
+    }
+}
+
+fn flatten(table: &Table, prefix: &str, out: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Table(table) => flatten(table, &path, out),
+            value => out.push((path, value.to_string())),
+        }
+    }
+}
+
+fn set_dotted(table: &mut Table, key: &str, value: Value) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            let entry = table
+                .entry(head.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+            if let Value::Table(inner) = entry {
+                set_dotted(inner, rest, value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+fn env_value(raw: &str, kind: EnvKind) -> Option<Value> {
+    match kind {
+        EnvKind::String => Some(Value::String(raw.to_string())),
+        EnvKind::Integer => raw.trim().parse().ok().map(Value::Integer),
+        EnvKind::Bool => match raw.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Some(Value::Boolean(true)),
+            "0" | "false" | "no" | "off" | "" => Some(Value::Boolean(false)),
+            _ => None,
+        },
+        EnvKind::List => Some(string_array(
+            raw.split(',').map(str::trim).filter(|s| !s.is_empty()),
+        )),
+        EnvKind::Paths => Some(string_array(raw.split(':').filter(|s| !s.is_empty()))),
+        EnvKind::Words => Some(string_array(raw.split_whitespace())),
+    }
+}
+
+// The inverse of `env_value`.
+fn env_raw(value: &Value, kind: EnvKind) -> Option<String> {
+    let join = |separator: &str| -> Option<String> {
+        let values: Option<Vec<&str>> = value.as_array()?.iter().map(Value::as_str).collect();
+        Some(values?.join(separator))
+    };
+    match kind {
+        EnvKind::String => value.as_str().map(str::to_string),
+        EnvKind::Integer => value.as_integer().map(|n| n.to_string()),
+        EnvKind::Bool => value.as_bool().map(|b| b.to_string()),
+        EnvKind::List => join(","),
+        EnvKind::Paths => join(":"),
+        EnvKind::Words => join(" "),
+    }
+}
+
+fn get_dotted<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    match key.split_once('.') {
+        Some((head, rest)) => get_dotted(table.get(head)?.as_table()?, rest),
+        None => table.get(key),
+    }
+}
+
+fn string_array<'a>(values: impl Iterator<Item = &'a str>) -> Value {
+    Value::Array(values.map(|v| Value::String(v.to_string())).collect())
+}
+
+fn cli_layers(matches: &ArgMatches, merged: &Table) -> Vec<(String, Table)> {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let strings = |id: &str| -> Vec<String> {
+        matches
+            .get_many::<String>(id)
+            .unwrap_or_default()
+            .cloned()
+            .collect()
+    };
+    let inherited = |key: &str| -> Vec<Value> {
+        match merged.get(key) {
+            Some(Value::Array(values)) => values.clone(),
+            _ => Vec::new(),
+        }
+    };
+    let single = |key: &str, value: Value| {
+        let mut table = Table::new();
+        table.insert(key.to_string(), value);
+        table
+    };
+
+    let mut layers = Vec::new();
+
+    if given("dir") {
+        let dirs: Vec<String> = matches
+            .get_many::<PathBuf>("dir")
+            .unwrap_or_default()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .collect();
+        layers.push((
+            "--dir".to_string(),
+            single("roots", string_array(dirs.iter().map(String::as_str))),
+        ));
+    }
+    for (id, flag, key) in [
+        ("min_depth", "--min-depth", "min_depth"),
+        ("max_depth", "--max-depth", "max_depth"),
+    ] {
+        if let Some(depth) = matches.get_one::<usize>(id).filter(|_| given(id)) {
+            layers.push((flag.to_string(), single(key, Value::Integer(*depth as i64))));
+        }
+    }
+
+    let clear = matches.get_flag("no_default_excludes");
+    if clear {
+        layers.push((
+            "--no-default-excludes".to_string(),
+            [
+                ("exclude".to_string(), Value::Array(Vec::new())),
+                ("exclude_regex".to_string(), Value::Array(Vec::new())),
+            ]
+            .into_iter()
+            .collect(),
+        ));
+    }
+    for (id, flag, key) in [
+        ("exclude", "--exclude", "exclude"),
+        ("exclude_regex", "--exclude-regex", "exclude_regex"),
+        ("include", "--include", "include"),
+        ("include_regex", "--include-regex", "include_regex"),
+    ] {
+        let added = strings(id);
+        if added.is_empty() {
+            continue;
+        }
+        let mut values = if clear && key.starts_with("exclude") {
+            Vec::new()
+        } else {
+            inherited(key)
+        };
+        values.extend(added.into_iter().map(Value::String));
+        layers.push((flag.to_string(), single(key, Value::Array(values))));
+    }
+
+    if matches.get_flag("projects") {
+        layers.push((
+            "--projects".to_string(),
+            single("projects", Value::Boolean(true)),
+        ));
+    }
+    let markers = strings("marker");
+    if !markers.is_empty() {
+        let mut table = single("projects", Value::Boolean(true));
+        table.insert(
+            "project_markers".to_string(),
+            string_array(markers.iter().map(String::as_str)),
+        );
+        layers.push(("--marker".to_string(), table));
+    }
+    for (id, flag, key) in [
+        ("select_1", "--select-1", "picker.select_1"),
+        ("exit_0", "--exit-0", "picker.exit_0"),
+    ] {
+        if matches.get_flag(id) {
+            let mut layer = Table::new();
+            set_dotted(&mut layer, key, Value::Boolean(true));
+            layers.push((flag.to_string(), layer));
+        }
+    }
+    if matches.get_flag("no_sessions") {
+        let mut layer = Table::new();
+        set_dotted(&mut layer, "picker.sessions", Value::Boolean(false));
+        layers.push(("--no-sessions".to_string(), layer));
+    }
+    if let Some(template) = matches
+        .get_one::<String>("template")
+        .filter(|_| given("template"))
+    {
+        let mut layer = Table::new();
+        set_dotted(
+            &mut layer,
+            "session.template",
+            Value::String(template.clone()),
+        );
+        layers.push(("--template".to_string(), layer));
+    }
+    if matches.get_flag("verbose") {
+        layers.push((
+            "--verbose".to_string(),
+            single("verbose", Value::Boolean(true)),
+        ));
+    }
+    if matches.get_flag("show_ignored") {
+        layers.push((
+            "--show-ignored".to_string(),
+            single("ignore_files", Value::Boolean(false)),
+        ));
+    }
+    if let Some(sort) = matches
+        .get_one::<SortOrder>("sort")
+        .filter(|_| given("sort"))
+        && let Ok(sort) = Value::try_from(sort)
+    {
+        layers.push(("--sort".to_string(), single("sort", sort)));
+    }
+    if let Some(backend) = matches
+        .get_one::<Backend>("picker")
+        .filter(|_| given("picker"))
+        && let Ok(backend) = Value::try_from(backend)
+    {
+        let mut layer = Table::new();
+        set_dotted(&mut layer, "picker.backend", backend);
+        layers.push(("--picker".to_string(), layer));
+    }
+
+    layers
+}
+
+fn invalid(e: impl fmt::Display) -> TshError {
+    TshError::InvalidConfig(e.to_string())
+}
+
+pub fn session_name(dir: &Path, session: &SessionConfig) -> Option<String> {
+    let basename = dir.file_name()?.to_str()?;
+    let parent = dir
+        .parent()
+        .and_then(|p| p.file_name())
+        .and_then(|n| n.to_str())
+        .unwrap_or("");
+    let relative = xdg::home_dir()
+        .and_then(|home| dir.strip_prefix(home).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| dir.to_path_buf());
+    let relative = relative.to_string_lossy();
+    let relative = relative.trim_start_matches('/');
+
+    let template = match session.naming {
+        Naming::Basename => "{basename}",
+        Naming::Parent => "{parent}_{basename}",
+        Naming::Path => "{path}",
+        Naming::Template => session.name_template.as_str(),
+    };
+
+    let name = template
+        .replace("{basename}", basename)
+        .replace("{parent}", parent)
+        .replace("{path}", &relative.replace('/', "_"));
+
+    Some(name.replace(['.', ':'], "_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+
+    fn strings(values: &[&str]) -> Value {
+        string_array(values.iter().copied())
+    }
+
+    // Layers a config-file table over the defaults and the given command line
+    // the way `Config::load` does, minus the environment.
+    fn layered(file: Table, args: &[&str]) -> (Table, BTreeMap<String, Origin>) {
+        let mut merged = Table::new();
+        let mut origins = BTreeMap::new();
+        let defaults = match Value::try_from(Config::default()).unwrap() {
+            Value::Table(table) => table,
+            _ => unreachable!(),
+        };
+        apply(&mut merged, defaults, &Origin::Default, &mut origins, "");
+        let path = PathBuf::from("/etc/tsh.toml");
+        apply(&mut merged, file, &Origin::File(path), &mut origins, "");
+
+        let matches = cli::build()
+            .try_get_matches_from(std::iter::once("tsh").chain(args.iter().copied()))
+            .unwrap();
+        for (flag, layer) in cli_layers(&matches, &merged) {
+            apply(&mut merged, layer, &Origin::Cli(flag), &mut origins, "");
+        }
+        (merged, origins)
+    }
+
+    #[test]
+    fn later_layers_win_and_record_their_origin() {
+        let file: Table = toml::from_str("max_depth = 4\n[picker]\nbackend = \"sk\"\n").unwrap();
+        let (merged, origins) = layered(file, &["--max-depth", "7"]);
+
+        assert_eq!(merged["max_depth"], Value::Integer(7));
+        assert_eq!(origins["max_depth"], Origin::Cli("--max-depth".to_string()));
+        assert_eq!(merged["picker"]["backend"], Value::String("sk".to_string()));
+        assert_eq!(
+            origins["picker.backend"],
+            Origin::File(PathBuf::from("/etc/tsh.toml"))
+        );
+        // Nested tables are merged key by key rather than replaced.
+        assert_eq!(merged["picker"]["select_1"], Value::Boolean(false));
+        assert_eq!(origins["picker.select_1"], Origin::Default);
+    }
+
+    #[test]
+    fn exclude_flags_extend_inherited_patterns() {
+        let file: Table = toml::from_str("exclude = [\"target\"]").unwrap();
+        let (merged, origins) = layered(file, &["--exclude", "build", "--exclude", "dist"]);
+
+        assert_eq!(merged["exclude"], strings(&["target", "build", "dist"]));
+        assert_eq!(origins["exclude"], Origin::Cli("--exclude".to_string()));
+    }
+
+    #[test]
+    fn no_default_excludes_keeps_only_command_line_patterns() {
+        let file: Table =
+            toml::from_str("exclude = [\"target\"]\nexclude_regex = [\"^tmp\"]").unwrap();
+        let (merged, origins) = layered(file, &["--no-default-excludes", "--exclude", "build"]);
+
+        assert_eq!(merged["exclude"], strings(&["build"]));
+        assert_eq!(origins["exclude"], Origin::Cli("--exclude".to_string()));
+        assert_eq!(merged["exclude_regex"], strings(&[]));
+        assert_eq!(
+            origins["exclude_regex"],
+            Origin::Cli("--no-default-excludes".to_string())
+        );
+    }
+
+    #[test]
+    fn command_line_settings_are_passed_on_as_env() {
+        let (merged, origins) = layered(
+            Table::new(),
+            &[
+                "--dir",
+                "/a",
+                "--dir",
+                "/b",
+                "--template",
+                "rust",
+                "--exclude",
+                "build",
+                "--max-depth",
+                "3",
+                "--projects",
+            ],
+        );
+        let mut config: Config = Value::Table(merged.clone()).try_into().unwrap();
+        config.origins = origins;
+        config.effective = merged;
+
+        let env: BTreeMap<&str, OsString> = config.child_env().into_iter().collect();
+        assert_eq!(env["TSH_ROOTS"], "/a:/b");
+        assert_eq!(env["TSH_TEMPLATE"], "rust");
+        assert_eq!(env["TSH_MAX_DEPTH"], "3");
+        assert_eq!(env["TSH_PROJECTS"], "true");
+        assert!(!env.contains_key("TSH_SORT"));
+        assert!(!env.contains_key("TSH_CONFIG"));
+
+        for (var, key, kind) in ENV_VARS {
+            if let Some(raw) = env.get(var) {
+                let parsed = env_value(raw.to_str().unwrap(), *kind);
+                assert_eq!(
+                    parsed.as_ref(),
+                    get_dotted(&config.effective, key),
+                    "{}",
+                    var
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn env_values_are_parsed_by_kind() {
+        assert_eq!(env_value(" 3 ", EnvKind::Integer), Some(Value::Integer(3)));
+        assert_eq!(env_value("three", EnvKind::Integer), None);
+        for raw in ["1", "true", "YES", "on"] {
+            assert_eq!(env_value(raw, EnvKind::Bool), Some(Value::Boolean(true)));
+        }
+        for raw in ["0", "false", "No", "off", ""] {
+            assert_eq!(env_value(raw, EnvKind::Bool), Some(Value::Boolean(false)));
+        }
+        assert_eq!(env_value("maybe", EnvKind::Bool), None);
+        assert_eq!(
+            env_value("target, node_modules,,", EnvKind::List),
+            Some(strings(&["target", "node_modules"]))
+        );
+        assert_eq!(
+            env_value("/a b::/c", EnvKind::Paths),
+            Some(strings(&["/a b", "/c"]))
+        );
+        assert_eq!(
+            env_value(" --height=40%  --reverse ", EnvKind::Words),
+            Some(strings(&["--height=40%", "--reverse"]))
+        );
+        assert_eq!(
+            env_value(" keep ", EnvKind::String),
+            Some(Value::String(" keep ".to_string()))
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use crate::TshError;
+use crate::config::SearchRoot;
+use crate::index;
+use crate::index::Index;
+use crate::walker::WalkOptions;
+use crate::xdg;
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+struct WatchedRoot {
+    index: Index,
+    options: WalkOptions,
+    dirty: bool,
+}
+
+struct Watcher {
+    inotify: Inotify,
+    by_wd: HashMap<WatchDescriptor, PathBuf>,
+    by_path: HashMap<PathBuf, WatchDescriptor>,
+    exhausted: bool,
+}
+
+impl Watcher {
+    fn add(&mut self, dir: &Path) {
+        if self.exhausted {
+            return;
+        }
+        let mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO
+            | WatchMask::ONLYDIR
+            | WatchMask::DONT_FOLLOW;
+
+        match self.inotify.watches().add(dir, mask) {
+            Ok(wd) => {
+                self.by_wd.insert(wd.clone(), dir.to_path_buf());
+                self.by_path.insert(dir.to_path_buf(), wd);
+            }
+            // Out of watches every further add fails the same way, so report
+            // it once instead of for each remaining directory.
+            Err(e) if e.kind() == io::ErrorKind::StorageFull => {
+                eprintln!(
+                    "inotify watch limit reached at {} (raise fs.inotify.max_user_watches); \
+                     newer directories are not watched",
+                    dir.display()
+                );
+                self.exhausted = true;
+            }
+            Err(e) => eprintln!("Failed to watch {}: {}", dir.display(), e),
+        }
+    }
+
+    // The new directory is watched before it is walked and walked again until
+    // nothing new shows up, so subdirectories created meanwhile are not missed.
+    fn add_tree(&mut self, root: &mut WatchedRoot, path: &Path) {
+        let known = self.by_path.contains_key(path);
+        if !known {
+            self.add(path);
+        }
+        let mut added = root.index.insert_tree(path, &root.options);
+        if added.is_empty() && !known {
+            self.forget(&[path.to_path_buf()], true);
+        }
+        while !added.is_empty() {
+            for dir in &added {
+                self.add(dir);
+            }
+            added = root.index.insert_tree(path, &root.options);
+        }
+    }
+
+    fn forget(&mut self, dirs: &[PathBuf], unwatch: bool) {
+        for dir in dirs {
+            if let Some(wd) = self.by_path.remove(dir) {
+                self.by_wd.remove(&wd);
+                if unwatch {
+                    let _ = self.inotify.watches().remove(wd);
+                }
+            }
+        }
+    }
+}
+
+pub fn socket_path() -> Result<PathBuf, TshError> {
+    let runtime = xdg::runtime_dir().ok_or_else(|| {
+        TshError::CommandFailed("Could not determine runtime directory".to_string())
+    })?;
+    Ok(runtime.join("tsh").join("daemon.sock"))
+}
+
+pub fn query(root: &Path, options: &WalkOptions) -> Option<Vec<PathBuf>> {
+    let mut stream = UnixStream::connect(socket_path().ok()?).ok()?;
+    stream.set_read_timeout(Some(QUERY_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(QUERY_TIMEOUT)).ok()?;
+
+    let mut request = format!("list\t{}\t", options.fingerprint()).into_bytes();
+    request.extend_from_slice(root.as_os_str().as_bytes());
+    request.push(b'\n');
+    stream.write_all(&request).ok()?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).ok()?;
+
+    let mut lines = response.split(|&b| b == b'\n');
+    if lines.next()? != b"ok" {
+        return None;
+    }
+
+    Some(
+        lines
+            .filter(|line| !line.is_empty())
+            .map(|line| PathBuf::from(OsStr::from_bytes(line)))
+            .collect(),
+    )
+}
+
+pub fn run(roots: &[SearchRoot]) -> Result<(), TshError> {
+    let socket = socket_path()?;
+    if UnixStream::connect(&socket).is_ok() {
+        return Err(TshError::CommandFailed(format!(
+            "tsh daemon is already listening on {}",
+            socket.display()
+        )));
+    }
+    if let Some(parent) = socket.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _ = fs::remove_file(&socket);
+
+    let mut watcher = Watcher {
+        inotify: Inotify::init()?,
+        by_wd: HashMap::new(),
+        by_path: HashMap::new(),
+        exhausted: false,
+    };
+
+    let mut watched = Vec::new();
+    for root in roots {
+        let (mut index, cached) = index::load_or_build(&root.path, &root.options)?;
+        if cached {
+            let stats = index.refresh(&root.options);
+            println!("Refreshed index for {}: {}", root.path.display(), stats);
+            index.save()?;
+        }
+        for dir in index.indexed() {
+            watcher.add(dir);
+        }
+        println!(
+            "Watching {} directories under {} ({})",
+            index.len(),
+            root.path.display(),
+            root.label
+        );
+        watched.push(WatchedRoot {
+            index,
+            options: root.options.clone(),
+            dirty: false,
+        });
+    }
+
+    let state = Arc::new(Mutex::new(watched));
+
+    let listener = UnixListener::bind(&socket)?;
+    println!("Listening on {}", socket.display());
+    {
+        let state = Arc::clone(&state);
+        thread::spawn(move || serve(listener, state));
+    }
+    {
+        let state = Arc::clone(&state);
+        thread::spawn(move || save_periodically(state));
+    }
+
+    let mut buffer = [0; 4096];
+    loop {
+        let events: Vec<(WatchDescriptor, EventMask, Option<PathBuf>)> = watcher
+            .inotify
+            .read_events_blocking(&mut buffer)?
+            .map(|event| (event.wd, event.mask, event.name.map(PathBuf::from)))
+            .collect();
+
+        let Ok(mut watched) = state.lock() else {
+            return Err(TshError::CommandFailed("Daemon state poisoned".to_string()));
+        };
+
+        for (wd, mask, name) in events {
+            if mask.contains(EventMask::Q_OVERFLOW) {
+                eprintln!("inotify queue overflowed, rescanning search roots");
+                for root in watched.iter_mut() {
+                    let stats = root.index.refresh(&root.options);
+                    println!(
+                        "Refreshed index for {}: {}",
+                        root.index.root.display(),
+                        stats
+                    );
+                    root.dirty = true;
+                    for dir in root.index.indexed() {
+                        watcher.add(dir);
+                    }
+                }
+                continue;
+            }
+
+            if mask.contains(EventMask::IGNORED) {
+                if let Some(dir) = watcher.by_wd.remove(&wd) {
+                    watcher.by_path.remove(&dir);
+                }
+                continue;
+            }
+
+            if !mask.contains(EventMask::ISDIR) {
+                continue;
+            }
+            let (Some(parent), Some(name)) = (watcher.by_wd.get(&wd), name) else {
+                continue;
+            };
+            let path = parent.join(name);
+
+            for root in watched
+                .iter_mut()
+                .filter(|r| path.starts_with(&r.index.root))
+            {
+                if mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                    watcher.add_tree(root, &path);
+                } else if mask.intersects(EventMask::DELETE | EventMask::MOVED_FROM) {
+                    let removed = root.index.remove_tree(&path);
+                    watcher.forget(&removed, mask.contains(EventMask::MOVED_FROM));
+                }
+                root.dirty = true;
+            }
+        }
+    }
+}
+
+fn serve(listener: UnixListener, state: Arc<Mutex<Vec<WatchedRoot>>>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        if let Err(e) = answer(stream, &state) {
+            eprintln!("Failed to answer query: {}", e);
+        }
+    }
+}
+
+fn answer(stream: UnixStream, state: &Mutex<Vec<WatchedRoot>>) -> Result<(), TshError> {
+    stream.set_read_timeout(Some(QUERY_TIMEOUT))?;
+
+    let mut request = Vec::new();
+    BufReader::new(&stream).read_until(b'\n', &mut request)?;
+    if request.pop() != Some(b'\n') {
+        return Ok(());
+    }
+
+    let mut fields = request.splitn(3, |&b| b == b'\t');
+    let (Some(b"list"), Some(fingerprint), Some(root)) =
+        (fields.next(), fields.next(), fields.next())
+    else {
+        (&stream).write_all(b"error\n")?;
+        return Ok(());
+    };
+    let root = Path::new(OsStr::from_bytes(root));
+
+    let directories = state.lock().ok().and_then(|watched| {
+        watched
+            .iter()
+            .find(|w| w.index.root == root && w.options.fingerprint().as_bytes() == fingerprint)
+            .map(|w| w.index.directories(&w.options))
+    });
+
+    let mut out = std::io::BufWriter::new(&stream);
+    match directories {
+        Some(directories) => {
+            out.write_all(b"ok\n")?;
+            for dir in directories {
+                out.write_all(dir.as_os_str().as_bytes())?;
+                out.write_all(b"\n")?;
+            }
+        }
+        None => out.write_all(b"unknown\n")?,
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+fn save_periodically(state: Arc<Mutex<Vec<WatchedRoot>>>) {
+    loop {
+        thread::sleep(SAVE_INTERVAL);
+        let Ok(mut watched) = state.lock() else {
+            return;
+        };
+        for root in watched.iter_mut().filter(|r| r.dirty) {
+            match root.index.save() {
+                Ok(()) => root.dirty = false,
+                Err(e) => eprintln!(
+                    "Failed to save index for {}: {}",
+                    root.index.root.display(),
+                    e
+                ),
+            }
+        }
+    }
+}
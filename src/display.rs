@@ -0,0 +1,145 @@
+use crate::candidate::{Candidate, FIELD_SEPARATOR};
+use crate::config::{Config, SearchRoot};
+use crate::git::Status;
+use crate::project::{self, ProjectType};
+use crate::xdg;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+// Sessions keep their own marker so they stand out even without icons; a
+// directory whose session is running gets a lighter one.
+const SESSION_MARKER: &str = "● ";
+const RUNNING_MARKER: &str = "○ ";
+const IDLE_MARKER: &str = "  ";
+const SESSION_ICON: &str = "\u{f489}";
+const FOLDER_ICON: &str = "\u{f07b}";
+const GIT_ICON: &str = "\u{e702}";
+
+// Keyed by project type name; types without an icon of their own, and
+// repositories of no known type, fall back to the git or folder icon.
+const PROJECT_ICONS: &[(&str, &str)] = &[
+    ("rust", "\u{e7a8}"),
+    ("node", "\u{e718}"),
+    ("go", "\u{e627}"),
+    ("python", "\u{e73c}"),
+    ("ruby", "\u{e739}"),
+    ("java", "\u{e738}"),
+    ("nix", "\u{f313}"),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathStyle {
+    #[default]
+    Home,
+    Relative,
+    Absolute,
+}
+
+pub struct Display {
+    style: PathStyle,
+    icons: bool,
+    home: Option<PathBuf>,
+    roots: Vec<(PathBuf, String)>,
+    running: HashSet<PathBuf>,
+    project_types: Vec<ProjectType>,
+}
+
+impl Display {
+    // Directories are marked as running by the start directory of the tmux
+    // sessions, which spares reading every candidate's project file for the
+    // session name it would get.
+    pub fn new(config: &Config, roots: &[SearchRoot], running: HashSet<PathBuf>) -> Display {
+        Display {
+            style: config.picker.paths,
+            icons: config.picker.icons,
+            home: xdg::home_dir(),
+            roots: roots
+                .iter()
+                .map(|root| (root.path.clone(), root.label.clone()))
+                .collect(),
+            running,
+            project_types: config.project_types.clone(),
+        }
+    }
+
+    pub fn line(&self, candidate: &Candidate, status: Option<&Status>) -> String {
+        let (marker, icon, text) = match candidate {
+            Candidate::Session(name) => (SESSION_MARKER, SESSION_ICON, name.clone()),
+            Candidate::Directory(dir) => {
+                let marker = if self.running.contains(dir) {
+                    RUNNING_MARKER
+                } else {
+                    IDLE_MARKER
+                };
+                let icon = if self.icons {
+                    self.project_icon(dir)
+                } else {
+                    ""
+                };
+                (marker, icon, self.path(dir))
+            }
+        };
+
+        let icon = if self.icons {
+            format!("{} ", icon)
+        } else {
+            String::new()
+        };
+        let status = status
+            .map(|status| format!("  {}", status))
+            .unwrap_or_default();
+        format!(
+            "{}{}{}{}{}{}",
+            marker,
+            icon,
+            text,
+            status,
+            FIELD_SEPARATOR,
+            candidate.key()
+        )
+    }
+
+    fn project_icon(&self, dir: &Path) -> &'static str {
+        let icon = project::detect(dir, &self.project_types).and_then(|detected| {
+            PROJECT_ICONS
+                .iter()
+                .find(|(name, _)| *name == detected.kind.name)
+                .map(|(_, icon)| *icon)
+        });
+        match icon {
+            Some(icon) => icon,
+            None if dir.join(".git").symlink_metadata().is_ok() => GIT_ICON,
+            None => FOLDER_ICON,
+        }
+    }
+
+    fn path(&self, dir: &Path) -> String {
+        if self.style == PathStyle::Relative
+            && let Some((root, label)) = self
+                .roots
+                .iter()
+                .filter(|(root, _)| dir.starts_with(root))
+                .max_by_key(|(root, _)| root.components().count())
+        {
+            let relative = dir.strip_prefix(root).unwrap_or(dir);
+            if relative.as_os_str().is_empty() {
+                return label.clone();
+            }
+            return format!("{}/{}", label, relative.display());
+        }
+
+        if self.style != PathStyle::Absolute
+            && let Some(home) = &self.home
+            && let Ok(relative) = dir.strip_prefix(home)
+        {
+            if relative.as_os_str().is_empty() {
+                return "~".to_string();
+            }
+            return format!("~/{}", relative.display());
+        }
+
+        dir.display().to_string()
+    }
+}
@@ -0,0 +1,109 @@
+use crate::TshError;
+use crate::xdg;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use std::path::Path;
+
+pub const DEFAULT_EXCLUDES: &[&str] = &["node_modules", ".git", ".cache", "tmp", "Library"];
+
+#[derive(Debug, Clone, Default)]
+pub struct PatternSet {
+    globs: Vec<String>,
+    regexes: Vec<Regex>,
+    name_globs: GlobSet,
+    path_globs: GlobSet,
+}
+
+impl PatternSet {
+    pub fn new(globs: &[String], regexes: &[String]) -> Result<PatternSet, TshError> {
+        let mut name_globs = GlobSetBuilder::new();
+        let mut path_globs = GlobSetBuilder::new();
+
+        for pattern in globs {
+            let expanded = expand_home(pattern);
+            let glob = GlobBuilder::new(&expanded)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| TshError::CommandFailed(format!("Invalid glob {}: {}", pattern, e)))?;
+            if expanded.contains('/') {
+                path_globs.add(glob);
+            } else {
+                name_globs.add(glob);
+            }
+        }
+
+        let regexes = regexes
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    TshError::CommandFailed(format!("Invalid regex {}: {}", pattern, e))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let build = |builder: GlobSetBuilder| {
+            builder
+                .build()
+                .map_err(|e| TshError::CommandFailed(format!("Invalid glob set: {}", e)))
+        };
+
+        Ok(PatternSet {
+            globs: globs.to_vec(),
+            regexes,
+            name_globs: build(name_globs)?,
+            path_globs: build(path_globs)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty() && self.regexes.is_empty()
+    }
+
+    pub fn matches(&self, dir: &Path) -> bool {
+        if dir
+            .file_name()
+            .is_some_and(|name| self.name_globs.is_match(name))
+        {
+            return true;
+        }
+        if self.path_globs.is_match(dir) {
+            return true;
+        }
+        if self.regexes.is_empty() {
+            return false;
+        }
+
+        let path = format!("{}/", dir.display());
+        self.regexes.iter().any(|re| re.is_match(&path))
+    }
+
+    pub fn describe(&self) -> String {
+        let regexes: Vec<&str> = self.regexes.iter().map(|re| re.as_str()).collect();
+        format!("[{}][{}]", self.globs.join(","), regexes.join(","))
+    }
+}
+
+pub fn expand_home(pattern: &str) -> String {
+    let Some(home) = xdg::home_dir() else {
+        return pattern.to_string();
+    };
+    match pattern.strip_prefix("~/") {
+        Some(rest) => format!("{}/{}", home.display(), rest),
+        None if pattern == "~" => home.display().to_string(),
+        None => pattern.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn home_is_expanded_alone_and_as_prefix() {
+        let home = xdg::home_dir().expect("HOME is set").display().to_string();
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/src"), format!("{}/src", home));
+        assert_eq!(expand_home("~user/src"), "~user/src");
+        assert_eq!(expand_home("/tmp/~"), "/tmp/~");
+    }
+}
@@ -0,0 +1,146 @@
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_WORD_START: i64 = 12;
+const PENALTY_GAP: i64 = 1;
+
+pub struct FuzzyMatch {
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+// Every whitespace-separated term has to match as a subsequence of the
+// haystack (smart case). Scores are summed and positions are char indices.
+pub fn find(haystack: &str, query: &str) -> Option<FuzzyMatch> {
+    let chars: Vec<char> = haystack.chars().collect();
+    let mut total = FuzzyMatch {
+        score: 0,
+        positions: Vec::new(),
+    };
+
+    for term in query.split_whitespace() {
+        let found = find_term(&chars, term)?;
+        total.score += found.score;
+        total.positions.extend(found.positions);
+    }
+
+    total.positions.sort_unstable();
+    total.positions.dedup();
+    total.score -= chars.len() as i64 / 4;
+    Some(total)
+}
+
+fn find_term(chars: &[char], term: &str) -> Option<FuzzyMatch> {
+    let ignore_case = !term.chars().any(char::is_uppercase);
+    let normalize = |c: char| {
+        if ignore_case {
+            c.to_ascii_lowercase()
+        } else {
+            c
+        }
+    };
+    let term: Vec<char> = term.chars().map(normalize).collect();
+    let first = *term.first()?;
+
+    // Try every occurrence of the first character as the starting point and
+    // keep the best greedy match, which favours tight runs near word starts.
+    chars
+        .iter()
+        .enumerate()
+        .filter(|&(_, &c)| normalize(c) == first)
+        .filter_map(|(start, _)| {
+            let mut positions = Vec::with_capacity(term.len());
+            let mut wanted = term.iter().peekable();
+            for (i, &c) in chars.iter().enumerate().skip(start) {
+                let Some(&&next) = wanted.peek() else {
+                    break;
+                };
+                if normalize(c) == next {
+                    positions.push(i);
+                    wanted.next();
+                }
+            }
+            if wanted.peek().is_some() {
+                return None;
+            }
+            Some(FuzzyMatch {
+                score: score(chars, &positions),
+                positions,
+            })
+        })
+        .max_by_key(|found| found.score)
+}
+
+fn score(chars: &[char], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+
+    for &i in positions {
+        score += SCORE_MATCH;
+        if i == 0 || !chars[i - 1].is_alphanumeric() {
+            score += BONUS_WORD_START;
+        }
+        match previous {
+            Some(p) if p + 1 == i => score += BONUS_CONSECUTIVE,
+            Some(p) => score -= PENALTY_GAP * (i - p - 1) as i64,
+            None => {}
+        }
+        previous = Some(i);
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(haystack: &str, query: &str) -> i64 {
+        find(haystack, query).expect("should match").score
+    }
+
+    #[test]
+    fn every_term_must_match_in_order() {
+        assert!(find("~/src/tsh", "tsh src").is_some());
+        assert!(find("~/src/tsh", "hst").is_none());
+        assert!(find("~/src/tsh", "tsh xyz").is_none());
+    }
+
+    #[test]
+    fn positions_are_char_indices() {
+        let found = find("~/çode/tsh", "tsh").unwrap();
+        assert_eq!(found.positions, vec![7, 8, 9]);
+
+        let found = find("~/src/tsh", "tsh sr").unwrap();
+        assert_eq!(found.positions, vec![2, 3, 6, 7, 8]);
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(find("~/src/Tsh", "tsh").is_some());
+        assert!(find("~/src/tsh", "Tsh").is_none());
+        assert!(find("~/src/Tsh", "Tsh").is_some());
+    }
+
+    #[test]
+    fn consecutive_matches_rank_above_scattered_ones() {
+        assert!(score("~/work/tsh", "tsh") > score("~/tools/shell", "tsh"));
+    }
+
+    #[test]
+    fn word_starts_rank_above_inner_matches() {
+        assert!(score("~/src/api", "api") > score("~/src/rapid", "api"));
+    }
+
+    #[test]
+    fn best_start_is_chosen() {
+        // The first 't' only leads to a scattered match inside a word; the
+        // later one to a run at a word start.
+        let found = find("~/xtxsxh/tsh", "tsh").unwrap();
+        assert_eq!(found.positions, vec![9, 10, 11]);
+    }
+
+    #[test]
+    fn shorter_paths_win_ties() {
+        assert!(score("~/tsh", "tsh") > score("~/very/long/prefix/tsh", "tsh"));
+    }
+}
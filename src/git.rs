@@ -0,0 +1,143 @@
+use crate::candidate::Candidate;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command as ProcessCommand, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const FALLBACK_WORKERS: usize = 4;
+const SHORT_OID: usize = 7;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub branch: String,
+    pub dirty: bool,
+    pub ahead: u32,
+    pub behind: u32,
+}
+
+type Job = (PathBuf, Sender<Status>);
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.branch, if self.dirty { "✗" } else { "✓" })?;
+        if self.ahead > 0 {
+            write!(f, " ↑{}", self.ahead)?;
+        }
+        if self.behind > 0 {
+            write!(f, " ↓{}", self.behind)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn status(dir: &Path) -> Option<Status> {
+    let output = ProcessCommand::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "--no-optional-locks",
+            "status",
+            "--porcelain=v2",
+            "--branch",
+        ])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut status = Status {
+        branch: String::new(),
+        dirty: false,
+        ahead: 0,
+        behind: 0,
+    };
+    let mut oid = "";
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in stdout.lines() {
+        let Some(header) = line.strip_prefix("# ") else {
+            status.dirty = true;
+            continue;
+        };
+        match header.split_once(' ') {
+            Some(("branch.oid", value)) => oid = value,
+            Some(("branch.head", value)) => status.branch = value.to_string(),
+            Some(("branch.ab", value)) => {
+                for count in value.split_whitespace() {
+                    if let Some(ahead) = count.strip_prefix('+') {
+                        status.ahead = ahead.parse().unwrap_or(0);
+                    } else if let Some(behind) = count.strip_prefix('-') {
+                        status.behind = behind.parse().unwrap_or(0);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if status.branch == "(detached)" {
+        status.branch = oid.chars().take(SHORT_OID).collect();
+    }
+
+    Some(status)
+}
+
+// Repositories are queried on a worker pool while the candidates pass through
+// in their original order. Only statuses that are ready before the budget runs
+// out are attached, so a slow repository never holds the list back for long.
+pub fn annotate(
+    candidates: impl Iterator<Item = Candidate> + Send + 'static,
+    budget: Duration,
+) -> impl Iterator<Item = (Candidate, Option<Status>)> {
+    let deadline = Instant::now() + budget;
+    let workers = thread::available_parallelism().map_or(FALLBACK_WORKERS, |n| n.get());
+
+    let (jobs_tx, jobs_rx) = mpsc::channel::<Job>();
+    let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+    for _ in 0..workers {
+        let jobs = Arc::clone(&jobs_rx);
+        thread::spawn(move || {
+            loop {
+                let Some((dir, tx)) = jobs.lock().ok().and_then(|jobs| jobs.recv().ok()) else {
+                    return;
+                };
+                if Instant::now() >= deadline {
+                    return;
+                }
+                if let Some(status) = status(&dir) {
+                    let _ = tx.send(status);
+                }
+            }
+        });
+    }
+
+    let (out_tx, out_rx) = mpsc::channel::<(Candidate, Option<Receiver<Status>>)>();
+    thread::spawn(move || {
+        for candidate in candidates {
+            let pending = match &candidate {
+                Candidate::Directory(dir)
+                    if Instant::now() < deadline && dir.join(".git").exists() =>
+                {
+                    let (tx, rx) = mpsc::channel();
+                    jobs_tx.send((dir.clone(), tx)).ok().map(|_| rx)
+                }
+                _ => None,
+            };
+            if out_tx.send((candidate, pending)).is_err() {
+                return;
+            }
+        }
+    });
+
+    out_rx.into_iter().map(move |(candidate, pending)| {
+        let status = pending.and_then(|rx| {
+            rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok()
+        });
+        (candidate, status)
+    })
+}
@@ -0,0 +1,188 @@
+use crate::TshError;
+use crate::xdg;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_TOTAL_RANK: f64 = 10000.0;
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Visit {
+    pub rank: f64,
+    pub last_visit: u64,
+}
+
+impl Visit {
+    pub fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_visit);
+        let weight = match age {
+            a if a < HOUR => 4.0,
+            a if a < DAY => 2.0,
+            a if a < WEEK => 0.5,
+            _ => 0.25,
+        };
+        self.rank * weight
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    visits: HashMap<PathBuf, Visit>,
+}
+
+impl History {
+    pub fn location() -> Result<PathBuf, TshError> {
+        let data = xdg::data_home().ok_or_else(|| {
+            TshError::CommandFailed("Could not determine data directory".to_string())
+        })?;
+        Ok(data.join("tsh").join("history"))
+    }
+
+    pub fn load() -> Result<History, TshError> {
+        let file = match File::open(History::location()?) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(History::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut visits = HashMap::new();
+        for line in BufReader::new(file).split(b'\n') {
+            let line = line?;
+            let mut fields = line.splitn(3, |&b| b == b'\t');
+            let (Some(rank), Some(last_visit), Some(path)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let (Some(rank), Some(last_visit)) = (parse_field(rank), parse_field(last_visit))
+            else {
+                continue;
+            };
+            visits.insert(
+                PathBuf::from(OsStr::from_bytes(path)),
+                Visit { rank, last_visit },
+            );
+        }
+
+        Ok(History { visits })
+    }
+
+    pub fn save(&self) -> Result<(), TshError> {
+        let path = History::location()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let tmp = path.with_extension("tmp");
+        {
+            let mut out = BufWriter::new(File::create(&tmp)?);
+            for (dir, visit) in &self.visits {
+                write!(out, "{}\t{}\t", visit.rank, visit.last_visit)?;
+                out.write_all(dir.as_os_str().as_bytes())?;
+                writeln!(out)?;
+            }
+            out.flush()?;
+        }
+        fs::rename(&tmp, &path)?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, dir: &Path) -> Option<&Visit> {
+        self.visits.get(dir)
+    }
+
+    pub fn record(&mut self, dir: &Path) {
+        let now = unix_now();
+        let visit = self.visits.entry(dir.to_path_buf()).or_insert(Visit {
+            rank: 0.0,
+            last_visit: now,
+        });
+        visit.rank += 1.0;
+        visit.last_visit = now;
+        self.age();
+    }
+
+    pub fn merge(&mut self, dir: PathBuf, visit: Visit) {
+        let entry = self.visits.entry(dir).or_insert(visit);
+        entry.rank = entry.rank.max(visit.rank);
+        entry.last_visit = entry.last_visit.max(visit.last_visit);
+    }
+
+    pub fn age(&mut self) {
+        // A non-finite rank would scale every other entry to zero or NaN.
+        self.visits
+            .retain(|_, visit| visit.rank.is_finite() && visit.rank >= 0.0);
+        let total: f64 = self.visits.values().map(|v| v.rank).sum();
+        if total <= MAX_TOTAL_RANK {
+            return;
+        }
+
+        let factor = 0.9 * MAX_TOTAL_RANK / total;
+        for visit in self.visits.values_mut() {
+            visit.rank *= factor;
+        }
+        self.visits.retain(|_, visit| visit.rank >= 1.0);
+    }
+}
+
+pub fn record_visit(dir: &Path) -> Result<(), TshError> {
+    let mut history = History::load()?;
+    history.record(dir);
+    history.save()
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn parse_field<T: std::str::FromStr>(field: &[u8]) -> Option<T> {
+    std::str::from_utf8(field).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aging_drops_invalid_ranks_and_keeps_the_rest() {
+        let mut history = History::default();
+        for (dir, rank) in [("/a", 6000.0), ("/b", 6000.0), ("/c", 0.5)] {
+            history.merge(
+                PathBuf::from(dir),
+                Visit {
+                    rank,
+                    last_visit: 1,
+                },
+            );
+        }
+        for rank in [f64::INFINITY, f64::NAN, -1.0] {
+            history.merge(
+                PathBuf::from(format!("/{}", rank)),
+                Visit {
+                    rank,
+                    last_visit: 1,
+                },
+            );
+        }
+
+        history.age();
+
+        let mut kept: Vec<&Path> = history.visits.keys().map(PathBuf::as_path).collect();
+        kept.sort();
+        assert_eq!(kept, [Path::new("/a"), Path::new("/b")]);
+        let a = history.get(Path::new("/a")).unwrap().rank;
+        let expected = 6000.0 * 0.9 * MAX_TOTAL_RANK / 12000.5;
+        assert!((a - expected).abs() < 1e-9, "rank {}", a);
+    }
+}
@@ -0,0 +1,268 @@
+use crate::TshError;
+use crate::history::{self, History, Visit};
+use crate::xdg;
+use clap::ValueEnum;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const ZOXIDE_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Source {
+    Zoxide,
+    Z,
+    Autojump,
+    Fasd,
+}
+
+impl Source {
+    pub fn all() -> &'static [Source] {
+        &[Source::Zoxide, Source::Z, Source::Autojump, Source::Fasd]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Zoxide => "zoxide",
+            Source::Z => "z",
+            Source::Autojump => "autojump",
+            Source::Fasd => "fasd",
+        }
+    }
+
+    pub fn default_location(&self) -> Option<PathBuf> {
+        let from_env = |var: &str| {
+            env::var_os(var)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        match self {
+            Source::Zoxide => from_env("_ZO_DATA_DIR")
+                .or_else(|| xdg::data_home().map(|data| data.join("zoxide")))
+                .map(|dir| dir.join("db.zo")),
+            Source::Z => {
+                from_env("_Z_DATA").or_else(|| xdg::home_dir().map(|home| home.join(".z")))
+            }
+            Source::Autojump => {
+                xdg::data_home().map(|data| data.join("autojump").join("autojump.txt"))
+            }
+            Source::Fasd => {
+                from_env("_FASD_DATA").or_else(|| xdg::home_dir().map(|home| home.join(".fasd")))
+            }
+        }
+    }
+
+    pub fn read(&self, path: &Path) -> Result<Vec<(PathBuf, Visit)>, TshError> {
+        match self {
+            Source::Zoxide => read_zoxide(&fs::read(path)?),
+            Source::Z | Source::Fasd => Ok(read_pipe_separated(&fs::read_to_string(path)?)),
+            Source::Autojump => {
+                let modified = fs::metadata(path)?
+                    .modified()?
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_else(|_| history::unix_now());
+                Ok(read_autojump(&fs::read_to_string(path)?, modified))
+            }
+        }
+    }
+}
+
+pub fn import(history: &mut History, source: Source, path: &Path) -> Result<usize, TshError> {
+    let mut imported = 0;
+    for (dir, visit) in source.read(path)? {
+        if dir.is_dir() {
+            history.merge(dir, visit);
+            imported += 1;
+        }
+    }
+    Ok(imported)
+}
+
+fn read_pipe_separated(contents: &str) -> Vec<(PathBuf, Visit)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, '|');
+            let last_visit = fields.next()?.trim().parse().ok()?;
+            let rank = valid_rank(fields.next()?.trim().parse().ok()?)?;
+            let path = fields.next()?;
+            Some((PathBuf::from(path), Visit { rank, last_visit }))
+        })
+        .collect()
+}
+
+fn read_autojump(contents: &str, last_visit: u64) -> Vec<(PathBuf, Visit)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (rank, path) = line.split_once('\t')?;
+            let rank = valid_rank(rank.trim().parse().ok()?)?;
+            Some((PathBuf::from(path), Visit { rank, last_visit }))
+        })
+        .collect()
+}
+
+fn read_zoxide(bytes: &[u8]) -> Result<Vec<(PathBuf, Visit)>, TshError> {
+    let invalid = || {
+        TshError::IoError(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unsupported zoxide database",
+        ))
+    };
+    let mut reader = ByteReader { bytes };
+
+    if reader.u32().ok_or_else(invalid)? != ZOXIDE_VERSION {
+        return Err(invalid());
+    }
+
+    let count = reader.u64().ok_or_else(invalid)?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let len = reader.u64().ok_or_else(invalid)? as usize;
+        let path = reader.take(len).ok_or_else(invalid)?;
+        let path = String::from_utf8_lossy(path).into_owned();
+        let rank = f64::from_bits(reader.u64().ok_or_else(invalid)?);
+        let last_visit = reader.u64().ok_or_else(invalid)?;
+        if let Some(rank) = valid_rank(rank) {
+            entries.push((PathBuf::from(path), Visit { rank, last_visit }));
+        }
+    }
+
+    Ok(entries)
+}
+
+// `f64` parsing accepts "inf" and "nan", and a single such rank would zero out
+// every other entry once the history is aged.
+fn valid_rank(rank: f64) -> Option<f64> {
+    (rank.is_finite() && rank >= 0.0).then_some(rank)
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)?.try_into().ok().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)?.try_into().ok().map(u64::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zoxide_db(version: u32, entries: &[(&str, f64, u64)]) -> Vec<u8> {
+        let mut bytes = version.to_le_bytes().to_vec();
+        bytes.extend((entries.len() as u64).to_le_bytes());
+        for (path, rank, last_visit) in entries {
+            bytes.extend((path.len() as u64).to_le_bytes());
+            bytes.extend(path.as_bytes());
+            bytes.extend(rank.to_bits().to_le_bytes());
+            bytes.extend(last_visit.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn visit(rank: f64, last_visit: u64) -> Visit {
+        Visit { rank, last_visit }
+    }
+
+    #[test]
+    fn zoxide_entries_are_decoded() {
+        let bytes = zoxide_db(
+            ZOXIDE_VERSION,
+            &[
+                ("/home/u/src", 12.5, 1_700_000_000),
+                ("/nan", f64::NAN, 1),
+                ("/inf", f64::INFINITY, 1),
+                ("/negative", -1.0, 1),
+                ("/tmp", 1.0, 42),
+            ],
+        );
+        let entries = read_zoxide(&bytes).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("/home/u/src"), visit(12.5, 1_700_000_000)),
+                (PathBuf::from("/tmp"), visit(1.0, 42)),
+            ]
+        );
+    }
+
+    #[test]
+    fn zoxide_empty_database() {
+        assert!(
+            read_zoxide(&zoxide_db(ZOXIDE_VERSION, &[]))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn zoxide_wrong_version_is_rejected() {
+        let bytes = zoxide_db(ZOXIDE_VERSION + 1, &[("/tmp", 1.0, 42)]);
+        assert!(read_zoxide(&bytes).is_err());
+    }
+
+    #[test]
+    fn zoxide_truncated_input_is_rejected() {
+        let bytes = zoxide_db(ZOXIDE_VERSION, &[("/home/u/src", 12.5, 1_700_000_000)]);
+        for len in [0, 3, 4, 11, 12, 19, 25, bytes.len() - 1] {
+            assert!(read_zoxide(&bytes[..len]).is_err(), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn zoxide_oversized_path_length_is_rejected() {
+        let mut bytes = ZOXIDE_VERSION.to_le_bytes().to_vec();
+        bytes.extend(1u64.to_le_bytes());
+        bytes.extend(u64::MAX.to_le_bytes());
+        assert!(read_zoxide(&bytes).is_err());
+    }
+
+    #[test]
+    fn pipe_separated_lines_are_parsed() {
+        let entries = read_pipe_separated(
+            "/home/u/src|12.5|1700000000\n/a|b/c|3|40\nbroken line\n/x|nan?|1\n\
+             /inf|inf|1\n/nan|NaN|1\n/negative|-2|1\n",
+        );
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("/home/u/src"), visit(12.5, 1_700_000_000)),
+                (PathBuf::from("/a|b/c"), visit(3.0, 40)),
+            ]
+        );
+    }
+
+    #[test]
+    fn autojump_lines_use_the_file_time() {
+        let entries = read_autojump(
+            "10.5\t/home/u/src\n\nnot-a-rank\t/x\ninf\t/y\nnan\t/z\n2\t/tmp\n",
+            99,
+        );
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("/home/u/src"), visit(10.5, 99)),
+                (PathBuf::from("/tmp"), visit(2.0, 99)),
+            ]
+        );
+    }
+}
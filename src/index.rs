@@ -0,0 +1,565 @@
+use crate::TshError;
+use crate::history::unix_now;
+use crate::walker::{self, WalkOptions};
+use crate::xdg;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+const INDEX_VERSION: &str = "tsh-index 1";
+
+#[derive(Debug)]
+pub struct Index {
+    pub path: PathBuf,
+    pub root: PathBuf,
+    pub fingerprint: String,
+    pub updated: u64,
+    entries: BTreeMap<PathBuf, u128>,
+}
+
+#[derive(Debug, Default)]
+pub struct RefreshStats {
+    pub rescanned: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl fmt::Display for RefreshStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} rescanned, {} added, {} removed",
+            self.rescanned, self.added, self.removed
+        )
+    }
+}
+
+impl Index {
+    pub fn location(root: &Path, options: &WalkOptions) -> Result<PathBuf, TshError> {
+        let cache = xdg::cache_home().ok_or_else(|| {
+            TshError::CommandFailed("Could not determine cache directory".to_string())
+        })?;
+        let key = fnv1a(&[
+            root.as_os_str().as_bytes(),
+            options.fingerprint().as_bytes(),
+        ]);
+        Ok(cache
+            .join("tsh")
+            .join("index")
+            .join(format!("{:016x}", key)))
+    }
+
+    pub fn build(root: &Path, options: &WalkOptions) -> Result<Index, TshError> {
+        Index::walk(root, options, |_| true).map(|(index, _)| index)
+    }
+
+    // Builds the index while handing every candidate directory to `found` as
+    // soon as the walker reaches it, in discovery order. Once `found` returns
+    // false the walk is abandoned and, being incomplete, no index is returned.
+    pub fn stream(
+        root: &Path,
+        options: &WalkOptions,
+        found: impl FnMut(PathBuf) -> bool,
+    ) -> Result<Option<Index>, TshError> {
+        Index::walk(root, options, found).map(|(index, complete)| complete.then_some(index))
+    }
+
+    fn walk(
+        root: &Path,
+        options: &WalkOptions,
+        mut found: impl FnMut(PathBuf) -> bool,
+    ) -> Result<(Index, bool), TshError> {
+        let walk_options = WalkOptions {
+            min_depth: 0,
+            ..options.clone()
+        };
+        let roots = [root.to_path_buf()];
+        let root_depth = root.components().count();
+
+        let mut entries = BTreeMap::new();
+        let mut complete = true;
+        thread::scope(|scope| {
+            let (tx, rx) = mpsc::channel();
+            scope.spawn(|| walker::stream_tree(&roots, &walk_options, tx));
+            // Breaking out drops the receiver, which makes the walker quit.
+            for dir in rx {
+                let Some(mtime) = dir_mtime(&dir) else {
+                    continue;
+                };
+                if options.is_candidate(&dir, dir.components().count() - root_depth)
+                    && !found(dir.clone())
+                {
+                    complete = false;
+                    break;
+                }
+                entries.insert(dir, mtime);
+            }
+        });
+
+        let index = Index {
+            path: Index::location(root, options)?,
+            root: root.to_path_buf(),
+            fingerprint: options.fingerprint(),
+            updated: unix_now(),
+            entries,
+        };
+        Ok((index, complete))
+    }
+
+    pub fn load(root: &Path, options: &WalkOptions) -> Result<Option<Index>, TshError> {
+        let path = Index::location(root, options)?;
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut lines = BufReader::new(file).split(b'\n');
+        let mut header = |name: &str| -> Option<Vec<u8>> {
+            let line = lines.next()?.ok()?;
+            let value = line.strip_prefix(name.as_bytes())?.strip_prefix(b"\t")?;
+            Some(value.to_vec())
+        };
+
+        if header("version").as_deref() != Some(INDEX_VERSION.as_bytes()) {
+            return Ok(None);
+        }
+        let Some(indexed_root) = header("root") else {
+            return Ok(None);
+        };
+        let Some(fingerprint) = header("options") else {
+            return Ok(None);
+        };
+        let updated = header("updated")
+            .and_then(|v| String::from_utf8(v).ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        if indexed_root != root.as_os_str().as_bytes()
+            || fingerprint != options.fingerprint().as_bytes()
+        {
+            return Ok(None);
+        }
+
+        let mut entries = BTreeMap::new();
+        for line in lines {
+            let line = line?;
+            let Some(tab) = line.iter().position(|&b| b == b'\t') else {
+                continue;
+            };
+            let Some(mtime) = std::str::from_utf8(&line[..tab])
+                .ok()
+                .and_then(|v| v.parse().ok())
+            else {
+                continue;
+            };
+            entries.insert(PathBuf::from(OsStr::from_bytes(&line[tab + 1..])), mtime);
+        }
+
+        Ok(Some(Index {
+            path,
+            root: root.to_path_buf(),
+            fingerprint: options.fingerprint(),
+            updated,
+            entries,
+        }))
+    }
+
+    pub fn save(&self) -> Result<(), TshError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut out = BufWriter::new(File::create(&tmp)?);
+            writeln!(out, "version\t{}", INDEX_VERSION)?;
+            out.write_all(b"root\t")?;
+            out.write_all(self.root.as_os_str().as_bytes())?;
+            writeln!(out)?;
+            writeln!(out, "options\t{}", self.fingerprint)?;
+            writeln!(out, "updated\t{}", self.updated)?;
+            for (dir, mtime) in &self.entries {
+                let bytes = dir.as_os_str().as_bytes();
+                if bytes.contains(&b'\n') {
+                    continue;
+                }
+                write!(out, "{}\t", mtime)?;
+                out.write_all(bytes)?;
+                writeln!(out)?;
+            }
+            out.flush()?;
+        }
+        fs::rename(&tmp, &self.path)?;
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn indexed(&self) -> impl Iterator<Item = &PathBuf> {
+        self.entries.keys()
+    }
+
+    pub fn directories(&self, options: &WalkOptions) -> Vec<PathBuf> {
+        if !options.projects_only() {
+            return self
+                .entries
+                .keys()
+                .filter(|dir| options.is_candidate(dir, self.depth_of(dir)))
+                .cloned()
+                .collect();
+        }
+
+        let mut projects: Vec<PathBuf> = Vec::new();
+        for dir in self.entries.keys() {
+            let depth = self.depth_of(dir);
+            let nested = projects
+                .last()
+                .is_some_and(|project| *project != self.root && dir.starts_with(project));
+            if !nested && options.is_candidate(dir, depth) {
+                projects.push(dir.clone());
+            }
+        }
+        projects
+    }
+
+    pub fn insert_tree(&mut self, dir: &Path, options: &WalkOptions) -> Vec<PathBuf> {
+        if !dir.starts_with(&self.root) || options.prunes(dir) {
+            return Vec::new();
+        }
+        if options.ignore_files
+            && dir.parent().is_some_and(|parent| {
+                !walker::list_children(parent, options)
+                    .iter()
+                    .any(|child| child == dir)
+            })
+        {
+            return Vec::new();
+        }
+
+        let depth = self.depth_of(dir);
+        if options.max_depth.is_some_and(|max| depth > max) {
+            return Vec::new();
+        }
+
+        let walk_options = WalkOptions {
+            min_depth: 0,
+            max_depth: options.max_depth.map(|max| max - depth),
+            ..options.clone()
+        };
+
+        let added: Vec<PathBuf> = walker::walk_tree(&[dir.to_path_buf()], &walk_options)
+            .into_iter()
+            .filter(|dir| !self.entries.contains_key(dir))
+            .collect();
+        for dir in &added {
+            if let Some(mtime) = dir_mtime(dir) {
+                self.entries.insert(dir.clone(), mtime);
+            }
+        }
+        self.touch(dir.parent().unwrap_or(dir));
+
+        added
+    }
+
+    pub fn remove_tree(&mut self, dir: &Path) -> Vec<PathBuf> {
+        let removed: Vec<PathBuf> = self
+            .entries
+            .range(dir.to_path_buf()..)
+            .map(|(path, _)| path)
+            .take_while(|path| path.starts_with(dir))
+            .cloned()
+            .collect();
+        for path in &removed {
+            self.entries.remove(path);
+        }
+        if let Some(parent) = dir.parent() {
+            self.touch(parent);
+        }
+
+        removed
+    }
+
+    pub fn touch(&mut self, dir: &Path) {
+        if let (Some(stored), Some(mtime)) = (self.entries.get_mut(dir), dir_mtime(dir)) {
+            *stored = mtime;
+        }
+        self.updated = unix_now();
+    }
+
+    fn depth_of(&self, dir: &Path) -> usize {
+        dir.components().count() - self.root.components().count()
+    }
+
+    pub fn refresh(&mut self, options: &WalkOptions) -> RefreshStats {
+        let mut children: HashMap<&Path, Vec<&Path>> = HashMap::new();
+        for dir in self.entries.keys() {
+            if let Some(parent) = dir.parent() {
+                children.entry(parent).or_default().push(dir);
+            }
+        }
+
+        let mut stats = RefreshStats::default();
+        let mut refreshed = BTreeMap::new();
+        let mut stack = vec![(self.root.clone(), 0)];
+
+        while let Some((dir, depth)) = stack.pop() {
+            let Some(mtime) = dir_mtime(&dir) else {
+                continue;
+            };
+
+            let descend = options.max_depth.is_none_or(|max| depth < max)
+                && !(depth > 0 && options.is_project(&dir));
+            if descend {
+                if self.entries.get(&dir) == Some(&mtime) {
+                    if let Some(known) = children.get(dir.as_path()) {
+                        stack.extend(known.iter().map(|child| (child.to_path_buf(), depth + 1)));
+                    }
+                } else {
+                    stats.rescanned += 1;
+                    stack.extend(
+                        walker::list_children(&dir, options)
+                            .into_iter()
+                            .map(|child| (child, depth + 1)),
+                    );
+                }
+            }
+
+            if !self.entries.contains_key(&dir) {
+                stats.added += 1;
+            }
+            refreshed.insert(dir, mtime);
+        }
+
+        stats.removed = self
+            .entries
+            .keys()
+            .filter(|dir| !refreshed.contains_key(*dir))
+            .count();
+        self.entries = refreshed;
+        self.updated = unix_now();
+
+        stats
+    }
+}
+
+pub fn load_or_build(root: &Path, options: &WalkOptions) -> Result<(Index, bool), TshError> {
+    match Index::load(root, options)? {
+        Some(index) => Ok((index, true)),
+        None => {
+            let index = Index::build(root, options)?;
+            index.save()?;
+            Ok((index, false))
+        }
+    }
+}
+
+fn dir_mtime(dir: &Path) -> Option<u128> {
+    let meta = fs::symlink_metadata(dir).ok()?;
+    if !meta.is_dir() {
+        return None;
+    }
+    meta.modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_nanos())
+}
+
+// Parts are separated by a NUL byte so that ("ab", "c") and ("a", "bc") differ.
+pub fn fnv1a(parts: &[&[u8]]) -> u64 {
+    parts
+        .iter()
+        .enumerate()
+        .flat_map(|(i, part)| (i > 0).then_some(&0u8).into_iter().chain(part.iter()))
+        .fold(0xcbf29ce484222325, |hash, &b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter;
+    use std::process;
+    use std::time::Duration;
+
+    // A scratch directory tree, removed again when the test ends.
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new(name: &str, dirs: &[&str]) -> Tree {
+            let root = std::env::temp_dir().join(format!("tsh-index-{}-{}", process::id(), name));
+            let _ = fs::remove_dir_all(&root);
+            let tree = Tree(root);
+            for dir in dirs {
+                tree.mkdir(dir);
+            }
+            tree
+        }
+
+        fn path(&self, rel: &str) -> PathBuf {
+            if rel.is_empty() {
+                self.0.clone()
+            } else {
+                self.0.join(rel)
+            }
+        }
+
+        fn mkdir(&self, rel: &str) {
+            fs::create_dir_all(self.path(rel)).unwrap();
+        }
+
+        fn relative(&self, dirs: impl IntoIterator<Item = PathBuf>) -> Vec<String> {
+            let mut dirs: Vec<String> = dirs
+                .into_iter()
+                .map(|dir| dir.strip_prefix(&self.0).unwrap().display().to_string())
+                .collect();
+            dirs.sort();
+            dirs
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn options(max_depth: Option<usize>, markers: &[&str]) -> WalkOptions {
+        WalkOptions {
+            max_depth,
+            threads: 1,
+            project_markers: markers.iter().map(|m| m.to_string()).collect(),
+            ..WalkOptions::default()
+        }
+    }
+
+    // Directory mtimes come from a coarse clock on some filesystems, so a change
+    // made right after indexing could otherwise go unnoticed.
+    fn settle() {
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    fn indexed(tree: &Tree, index: &Index) -> Vec<String> {
+        tree.relative(index.indexed().cloned())
+    }
+
+    #[test]
+    fn refresh_rescans_only_changed_directories() {
+        let tree = Tree::new("refresh", &["a/x", "b", "c/y"]);
+        let options = options(None, &[]);
+        let mut index = Index::build(&tree.0, &options).unwrap();
+        assert_eq!(indexed(&tree, &index), ["", "a", "a/x", "b", "c", "c/y"]);
+
+        settle();
+        tree.mkdir("a/z/deep");
+        fs::remove_dir(tree.path("b")).unwrap();
+        let stats = index.refresh(&options);
+
+        assert_eq!(
+            indexed(&tree, &index),
+            ["", "a", "a/x", "a/z", "a/z/deep", "c", "c/y"]
+        );
+        // The root lost b and a gained z; z itself is new and listed as well.
+        assert_eq!(stats.rescanned, 4);
+        assert_eq!(stats.added, 2);
+        assert_eq!(stats.removed, 1);
+
+        let stats = index.refresh(&options);
+        assert_eq!((stats.rescanned, stats.added, stats.removed), (0, 0, 0));
+    }
+
+    #[test]
+    fn refresh_respects_max_depth_and_projects() {
+        let tree = Tree::new("refresh-limits", &["a/b", "p/src"]);
+        fs::write(tree.path("p/Cargo.toml"), "").unwrap();
+        let options = options(Some(2), &["Cargo.toml"]);
+        let mut index = Index::build(&tree.0, &options).unwrap();
+        assert_eq!(indexed(&tree, &index), ["", "a", "a/b", "p"]);
+
+        settle();
+        tree.mkdir("a/b/c");
+        tree.mkdir("p/tests");
+        tree.mkdir("q");
+        let stats = index.refresh(&options);
+        assert_eq!(indexed(&tree, &index), ["", "a", "a/b", "p", "q"]);
+        assert_eq!(stats.added, 1);
+        assert_eq!(stats.removed, 0);
+    }
+
+    #[test]
+    fn insert_tree_adds_new_directories_up_to_max_depth() {
+        let tree = Tree::new("insert", &["a"]);
+        let options = options(Some(3), &[]);
+        let mut index = Index::build(&tree.0, &options).unwrap();
+
+        settle();
+        tree.mkdir("a/b/c/d/e");
+        let added = index.insert_tree(&tree.path("a/b"), &options);
+        assert_eq!(tree.relative(added), ["a/b", "a/b/c"]);
+        assert_eq!(indexed(&tree, &index), ["", "a", "a/b", "a/b/c"]);
+
+        // Already indexed directories are not reported again.
+        assert!(index.insert_tree(&tree.path("a/b"), &options).is_empty());
+        // Neither are directories below max_depth or outside the root.
+        assert!(
+            index
+                .insert_tree(&tree.path("a/b/c/d"), &options)
+                .is_empty()
+        );
+        assert!(index.insert_tree(Path::new("/"), &options).is_empty());
+    }
+
+    #[test]
+    fn insert_tree_skips_excluded_directories() {
+        let tree = Tree::new("insert-excluded", &[]);
+        let mut options = options(None, &[]);
+        options.exclude = filter::PatternSet::new(&["node_modules".to_string()], &[]).unwrap();
+        let mut index = Index::build(&tree.0, &options).unwrap();
+
+        tree.mkdir("node_modules/pkg");
+        tree.mkdir("src/node_modules");
+        assert!(
+            index
+                .insert_tree(&tree.path("node_modules"), &options)
+                .is_empty()
+        );
+        let added = index.insert_tree(&tree.path("src"), &options);
+        assert_eq!(tree.relative(added), ["src"]);
+    }
+
+    #[test]
+    fn remove_tree_removes_only_the_subtree() {
+        let tree = Tree::new("remove", &["a/b/c", "a-x", "a.x", "ab"]);
+        let options = options(None, &[]);
+        let mut index = Index::build(&tree.0, &options).unwrap();
+
+        let removed = index.remove_tree(&tree.path("a"));
+        assert_eq!(tree.relative(removed), ["a", "a/b", "a/b/c"]);
+        assert_eq!(indexed(&tree, &index), ["", "a-x", "a.x", "ab"]);
+        assert!(index.remove_tree(&tree.path("a")).is_empty());
+    }
+
+    #[test]
+    fn directories_lists_outermost_projects() {
+        let tree = Tree::new("projects", &["p/nested", "plain/q"]);
+        fs::write(tree.path("p/Cargo.toml"), "").unwrap();
+        fs::write(tree.path("p/nested/Cargo.toml"), "").unwrap();
+        fs::write(tree.path("plain/q/go.mod"), "").unwrap();
+        let options = options(None, &["Cargo.toml", "go.mod"]);
+        let index = Index::build(&tree.0, &options).unwrap();
+
+        assert_eq!(tree.relative(index.directories(&options)), ["p", "plain/q"]);
+    }
+}
@@ -0,0 +1,314 @@
+use crate::TshError;
+use crate::config::{self, Config, Origin, SessionConfig};
+use crate::filter;
+use crate::index;
+use crate::project;
+use crate::tmux;
+use crate::xdg;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+// Checked in order inside the selected directory.
+pub const PROJECT_FILES: &[&str] = &[".tsh.toml", ".tsh.yaml", ".tsh.yml"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Template {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<WindowConfig>,
+}
+
+// A layout committed to a repository; like a template, plus the name the
+// session should get.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectFile {
+    #[serde(skip)]
+    pub path: PathBuf,
+    #[serde(skip)]
+    pub contents: String,
+    pub name: Option<String>,
+    pub env: BTreeMap<String, String>,
+    pub windows: Vec<WindowConfig>,
+}
+
+pub struct Layout {
+    pub source: String,
+    pub template: Template,
+    // Set when the layout comes from a project file, whose commands only run
+    // once the user has allowed that exact file.
+    pub project: Option<(PathBuf, String)>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub panes: Vec<PaneConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaneConfig {
+    pub split: Split,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+// Named after the divider line like tmux does: a horizontal split places the
+// new pane to the right, a vertical one below.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Split {
+    Horizontal,
+    #[default]
+    Vertical,
+}
+
+impl Template {
+    pub fn commands(&self) -> Vec<&str> {
+        self.windows
+            .iter()
+            .flat_map(|window| {
+                window
+                    .command
+                    .iter()
+                    .chain(window.panes.iter().filter_map(|pane| pane.command.as_ref()))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn clear_untrusted(&mut self) {
+        self.env.clear();
+        for window in &mut self.windows {
+            window.command = None;
+            for pane in &mut window.panes {
+                pane.command = None;
+            }
+        }
+    }
+}
+
+pub fn load_project(dir: &Path) -> Result<Option<ProjectFile>, TshError> {
+    for file in PROJECT_FILES {
+        let path = dir.join(file);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let parsed = if file.ends_with(".toml") {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            serde_yaml::from_str(&contents).map_err(|e| e.to_string())
+        };
+        let mut project: ProjectFile =
+            parsed.map_err(|e| TshError::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+        project.path = path;
+        project.contents = contents;
+        return Ok(Some(project));
+    }
+    Ok(None)
+}
+
+// An explicit --template wins over the project file, then over the template
+// matching the detected project type, then over a default template from the
+// config file or environment.
+pub fn resolve(dir: &Path, config: &Config) -> Result<Option<Layout>, TshError> {
+    let named = config.template()?.map(|(name, template)| Layout {
+        source: format!("template '{}'", name),
+        template: template.clone(),
+        project: None,
+    });
+    if matches!(config.origin("session.template"), Origin::Cli(_)) {
+        return Ok(named);
+    }
+
+    if let Some(project) = load_project(dir)? {
+        return Ok(Some(Layout {
+            source: project.path.display().to_string(),
+            template: Template {
+                env: project.env,
+                windows: project.windows,
+            },
+            project: Some((project.path, project.contents)),
+        }));
+    }
+
+    if let Some(detected) = project::detect(dir, &config.project_types)
+        && let Some((name, template)) = config
+            .templates
+            .get_key_value(detected.kind.template_name())
+    {
+        return Ok(Some(Layout {
+            source: format!("template '{}' for {} project", name, detected.kind.name),
+            template: template.clone(),
+            project: None,
+        }));
+    }
+    Ok(named)
+}
+
+pub fn session_name(dir: &Path, session: &SessionConfig) -> Option<String> {
+    match load_project(dir)
+        .ok()
+        .flatten()
+        .and_then(|project| project.name)
+    {
+        Some(name) => Some(name.replace(['.', ':'], "_")),
+        None => config::session_name(dir, session),
+    }
+}
+
+// Like `direnv allow`: a repository's project file may only run commands or
+// set environment variables (which shells act on, e.g. PROMPT_COMMAND or
+// BASH_ENV) after the user approved its current contents, which are kept as a
+// copy under the data directory. Otherwise the windows and panes are still
+// created, just without either.
+pub fn approve(layout: &mut Layout) -> Result<(), TshError> {
+    let Some((path, contents)) = &layout.project else {
+        return Ok(());
+    };
+    let commands = layout.template.commands();
+    let env = &layout.template.env;
+    if (commands.is_empty() && env.is_empty())
+        || allowed(path)?.as_deref() == Some(contents.as_str())
+    {
+        return Ok(());
+    }
+
+    if io::stdin().is_terminal() && io::stderr().is_terminal() {
+        if !env.is_empty() {
+            eprintln!("{} wants to set:", path.display());
+            for (key, value) in env {
+                eprintln!("  {}={}", key, value);
+            }
+        }
+        if !commands.is_empty() {
+            eprintln!("{} wants to run:", path.display());
+            for command in &commands {
+                eprintln!("  {}", command);
+            }
+        }
+        eprint!("Allow this? [y/N] ");
+        io::stderr().flush()?;
+
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+            return allow(path, contents);
+        }
+        eprintln!("Skipping commands and environment from {}", path.display());
+    } else {
+        eprintln!(
+            "Skipping commands and environment from {} until they are allowed from a terminal",
+            path.display()
+        );
+    }
+    layout.template.clear_untrusted();
+    Ok(())
+}
+
+fn allowed_location(path: &Path) -> Result<PathBuf, TshError> {
+    let data = xdg::data_home()
+        .ok_or_else(|| TshError::CommandFailed("Could not determine data directory".to_string()))?;
+    let key = index::fnv1a(&[path.as_os_str().as_bytes()]);
+    Ok(data
+        .join("tsh")
+        .join("allowed")
+        .join(format!("{:016x}", key)))
+}
+
+fn allowed(path: &Path) -> Result<Option<String>, TshError> {
+    match fs::read_to_string(allowed_location(path)?) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn allow(path: &Path, contents: &str) -> Result<(), TshError> {
+    let location = allowed_location(path)?;
+    if let Some(parent) = location.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = location.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, &location)?;
+    Ok(())
+}
+
+// Creates the session detached with one tmux window per configured window; the
+// first window's first pane is the one the session starts with.
+pub fn create_session(name: &str, dir: &Path, template: &Template) -> Result<(), TshError> {
+    let env: Vec<String> = template
+        .env
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    let default_window = [WindowConfig::default()];
+    let windows = if template.windows.is_empty() {
+        &default_window[..]
+    } else {
+        &template.windows
+    };
+
+    for (i, window) in windows.iter().enumerate() {
+        let window_dir = resolve_dir(dir, window.dir.as_deref());
+        let pane = if i == 0 {
+            tmux::new_session_pane(name, window.name.as_deref(), &window_dir, &env)?
+        } else {
+            tmux::new_window_pane(name, window.name.as_deref(), &window_dir, &env)?
+        };
+        if let Some(command) = &window.command {
+            tmux::send_keys(&pane, command)?;
+        }
+
+        for split in &window.panes {
+            let split_dir = resolve_dir(&window_dir, split.dir.as_deref());
+            let pane = tmux::split_pane(
+                &pane,
+                split.split == Split::Horizontal,
+                split.size.as_deref(),
+                &split_dir,
+                &env,
+            )?;
+            if let Some(command) = &split.command {
+                tmux::send_keys(&pane, command)?;
+            }
+        }
+
+        if let Some(layout) = &window.layout {
+            tmux::select_layout(&pane, layout)?;
+        }
+        tmux::select_pane(&pane)?;
+    }
+
+    Ok(())
+}
+
+fn resolve_dir(base: &Path, dir: Option<&str>) -> PathBuf {
+    match dir {
+        Some(dir) => base.join(filter::expand_home(dir)),
+        None => base.to_path_buf(),
+    }
+}
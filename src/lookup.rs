@@ -0,0 +1,155 @@
+use crate::TshError;
+use crate::config::SearchRoot;
+use crate::filter;
+use crate::fuzzy;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+const REGEX_PREFIX: &str = "re:";
+const AMBIGUOUS_LISTED: usize = 10;
+
+enum Pattern {
+    Name(PathBuf),
+    Glob(GlobMatcher, GlobTarget),
+    Regex(Regex),
+}
+
+enum GlobTarget {
+    Name,
+    Relative,
+    Absolute,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Pattern, TshError> {
+        if let Some(regex) = pattern.strip_prefix(REGEX_PREFIX) {
+            return Regex::new(regex)
+                .map(Pattern::Regex)
+                .map_err(|e| TshError::CommandFailed(format!("Invalid regex {}: {}", regex, e)));
+        }
+
+        if !pattern.contains(['*', '?', '[', '{']) {
+            return Ok(Pattern::Name(PathBuf::from(filter::expand_home(pattern))));
+        }
+
+        let target = if pattern.starts_with('/') || pattern.starts_with("~/") {
+            GlobTarget::Absolute
+        } else if pattern.contains('/') {
+            GlobTarget::Relative
+        } else {
+            GlobTarget::Name
+        };
+        let expanded = filter::expand_home(pattern);
+        let matcher = GlobBuilder::new(&expanded)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| TshError::CommandFailed(format!("Invalid glob {}: {}", pattern, e)))?
+            .compile_matcher();
+        Ok(Pattern::Glob(matcher, target))
+    }
+}
+
+pub struct Match {
+    pub pattern: String,
+    pub dirs: Vec<PathBuf>,
+    pub fuzzy: bool,
+}
+
+pub fn resolve(
+    patterns: &[String],
+    roots: &[SearchRoot],
+    candidates: &[PathBuf],
+) -> Result<Vec<Match>, TshError> {
+    let relative = |dir: &Path| -> PathBuf {
+        roots
+            .iter()
+            .find_map(|root| dir.strip_prefix(&root.path).ok())
+            .unwrap_or(dir)
+            .to_path_buf()
+    };
+
+    patterns
+        .iter()
+        .map(|pattern| {
+            let (dirs, fuzzy) = match Pattern::parse(pattern)? {
+                Pattern::Regex(regex) => (
+                    select(candidates, |dir| regex.is_match(&dir.to_string_lossy())),
+                    false,
+                ),
+                Pattern::Glob(matcher, target) => (
+                    select(candidates, |dir| match target {
+                        GlobTarget::Name => {
+                            dir.file_name().is_some_and(|name| matcher.is_match(name))
+                        }
+                        GlobTarget::Relative => matcher.is_match(relative(dir)),
+                        GlobTarget::Absolute => matcher.is_match(dir),
+                    }),
+                    false,
+                ),
+                Pattern::Name(name) => {
+                    let exact = select(candidates, |dir| dir.ends_with(&name));
+                    if exact.is_empty() {
+                        (fuzzy_select(candidates, pattern, relative), true)
+                    } else {
+                        (exact, false)
+                    }
+                }
+            };
+            Ok(Match {
+                pattern: pattern.clone(),
+                dirs,
+                fuzzy,
+            })
+        })
+        .collect()
+}
+
+pub fn report_ambiguous(found: &Match) {
+    let kind = if found.fuzzy {
+        "fuzzy-matches"
+    } else {
+        "matches"
+    };
+    eprintln!(
+        "'{}' {} {} directories:",
+        found.pattern,
+        kind,
+        found.dirs.len()
+    );
+    for dir in found.dirs.iter().take(AMBIGUOUS_LISTED) {
+        eprintln!("  {}", dir.display());
+    }
+    if found.dirs.len() > AMBIGUOUS_LISTED {
+        eprintln!("  ... and {} more", found.dirs.len() - AMBIGUOUS_LISTED);
+    }
+}
+
+fn select(candidates: &[PathBuf], matches: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    candidates
+        .iter()
+        .filter(|dir| matches(dir))
+        .cloned()
+        .collect()
+}
+
+fn fuzzy_select(
+    candidates: &[PathBuf],
+    query: &str,
+    relative: impl Fn(&Path) -> PathBuf,
+) -> Vec<PathBuf> {
+    let mut scored: Vec<(i64, &PathBuf)> = candidates
+        .iter()
+        .filter_map(|dir| {
+            let haystack = if query.contains('/') {
+                relative(dir).to_string_lossy().into_owned()
+            } else {
+                dir.file_name()?.to_string_lossy().into_owned()
+            };
+            Some((fuzzy::find(&haystack, query)?.score, dir))
+        })
+        .collect();
+
+    scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(_, dir)| dir.clone()).collect()
+}
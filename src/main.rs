@@ -1,20 +1,57 @@
-use clap::{value_parser, Arg, ArgAction, Command};
-use regex::Regex;
+mod builtin;
+mod candidate;
+mod cli;
+mod config;
+mod daemon;
+mod display;
+mod filter;
+mod fuzzy;
+mod git;
+mod history;
+mod import;
+mod index;
+mod layout;
+mod lookup;
+mod picker;
+mod preview;
+mod project;
+mod sort;
+mod tmux;
+mod walker;
+mod xdg;
+
+use candidate::Candidate;
+use clap::ArgMatches;
+use config::{Config, Origin, SearchRoot};
+use display::Display;
+use history::History;
+use index::Index;
+use layout::Layout;
+use picker::Action;
+use sort::SortOrder;
+use std::collections::HashSet;
 use std::env;
 use std::error::Error;
 use std::fmt;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command as ProcessCommand, Stdio};
+use std::process::{self, Command as ProcessCommand};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
 use which::which;
 
+type Refresh = JoinHandle<Result<(), TshError>>;
+
+const EXIT_NO_MATCH: i32 = 3;
+
 #[derive(Debug)]
 enum TshError {
     IoError(io::Error),
     MissingDependencies(Vec<String>),
     CommandFailed(String),
     NoDirectoriesFound,
-    UserCancelled,
+    InvalidConfig(String),
 }
 
 impl fmt::Display for TshError {
@@ -24,7 +61,16 @@ impl fmt::Display for TshError {
             TshError::MissingDependencies(deps) => write!(f, "MissingDependencies: {:?}", deps),
             TshError::CommandFailed(cmd) => write!(f, "Command failed: {}", cmd),
             TshError::NoDirectoriesFound => write!(f, "No directories found"),
-            TshError::UserCancelled => write!(f, "Operation cancelled by user"),
+            TshError::InvalidConfig(msg) => write!(f, "Invalid configuration: {}", msg),
+        }
+    }
+}
+
+impl TshError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            TshError::NoDirectoriesFound => EXIT_NO_MATCH,
+            _ => 1,
         }
     }
 }
@@ -37,23 +83,43 @@ impl From<io::Error> for TshError {
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let matches = Command::new("tsh")
-        .version("0.1.0")
-        .about("Tmux Session Handler - Select directories with fzf and create tmux sessions")
-        .arg(Arg::new("directory").action(ArgAction::Append))
-        .arg(
-            Arg::new("dir")
-                .short('d')
-                .long("dir")
-                .value_name("PATH")
-                .num_args(1)
-                .help("Set custom directory to search in")
-                .value_parser(value_parser!(PathBuf)),
-        )
-        .get_matches();
-
-    check_dependencies(&["fzf", "tmux"])?;
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(), TshError> {
+    let matches = cli::parse(env::args_os()).unwrap_or_else(|e| e.exit());
+
+    let mut leaf = &matches;
+    while let Some((_, sub)) = leaf.subcommand() {
+        leaf = sub;
+    }
+    let config = Config::load(leaf)?;
+
+    match matches.subcommand() {
+        Some(("index", index_matches)) => return run_index_command(index_matches, &config),
+        Some(("import", import_matches)) => return run_import_command(import_matches),
+        Some(("config", _)) => {
+            print!("{}", config.show());
+            return Ok(());
+        }
+        Some(("preview", preview_matches)) => {
+            let candidate = preview_matches
+                .get_one::<String>("candidate")
+                .map_or(".", String::as_str);
+            return preview::run(&Candidate::parse(candidate), &config);
+        }
+        Some(("daemon", _)) => return daemon::run(&config.search_roots()?),
+        _ => {}
+    }
+
+    let mut dependencies = vec!["tmux"];
+    dependencies.extend(config.picker.backend.resolve().picker().command());
+    check_dependencies(&dependencies)?;
+    config.template()?;
 
     let directories: Vec<String> = matches
         .get_many::<String>("directory")
@@ -61,16 +127,54 @@ fn main() -> Result<(), Box<dyn Error>> {
         .cloned()
         .collect();
 
-    let dir_option = matches.get_one::<PathBuf>("dir");
+    // Cached indexes are refreshed in the background while the picker is
+    // open; they are saved before exiting rather than holding up the session.
+    let mut refreshes = Vec::new();
+    let result = select_and_open(&directories, &config, &mut refreshes);
+    for refresh in refreshes {
+        if let Ok(Err(e)) = refresh.join() {
+            eprintln!("Failed to refresh directory index: {}", e);
+        }
+    }
+    result
+}
 
-    let selected_dir = find_and_select_directory(&directories, dir_option)?;
+fn select_and_open(
+    directories: &[String],
+    config: &Config,
+    refreshes: &mut Vec<Refresh>,
+) -> Result<(), TshError> {
+    // Kill and rename change the session list, so the picker is reopened.
+    loop {
+        let Some((action, candidates)) = find_and_select_directory(directories, config, refreshes)?
+        else {
+            println!("No directory selected. Exiting.");
+            return Ok(());
+        };
 
-    match selected_dir {
-        Some(dir) => create_tmux_session(&dir)?,
-        None => println!("No directory selected. Exiting."),
+        match action {
+            Action::Open => return open_candidates(&candidates, config),
+            Action::Detached => return start_sessions(&candidates, config).map(|_| ()),
+            Action::NewWindow => {
+                for candidate in &candidates {
+                    open_tmux_window(candidate, config)?;
+                }
+                return Ok(());
+            }
+            Action::Kill | Action::Rename => {
+                for candidate in &candidates {
+                    match running_session(candidate, config)? {
+                        Some(name) if action == Action::Kill => {
+                            tmux::kill_session(&name)?;
+                            println!("Killed session '{}'", name);
+                        }
+                        Some(name) => rename_tmux_session(&name)?,
+                        None => eprintln!("No running session for {}", candidate.key()),
+                    }
+                }
+            }
+        }
     }
-
-    Ok(())
 }
 
 fn check_dependencies(deps: &[&str]) -> Result<(), TshError> {
@@ -87,263 +191,444 @@ fn check_dependencies(deps: &[&str]) -> Result<(), TshError> {
     }
 }
 
-fn find_and_select_directory(
-    directories: &[String],
-    custom_dir: Option<&PathBuf>,
-) -> Result<Option<PathBuf>, TshError> {
-    let home_dir = env::var("HOME")
-        .map(PathBuf::from)
-        .or_else(|_| env::current_dir())
-        .map_err(|e| TshError::IoError(e))?;
-
-    if !directories.is_empty() {
-        let mut search_paths = Vec::new();
-
-        for search_dir in directories {
-            let output = ProcessCommand::new("find")
-                .arg(&home_dir)
-                .arg("-type")
-                .arg("d")
-                .arg("-name")
-                .arg(search_dir)
-                .output()?;
-
-            if output.status.success() {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                for line in stdout.lines() {
-                    if !line.is_empty() {
-                        search_paths.push(line.to_string());
+fn run_index_command(matches: &ArgMatches, config: &Config) -> Result<(), TshError> {
+    let Some((action, _)) = matches.subcommand() else {
+        return Ok(());
+    };
+
+    for (i, root) in config.search_roots()?.iter().enumerate() {
+        match action {
+            "rebuild" => {
+                let started = Instant::now();
+                let index = Index::build(&root.path, &root.options)?;
+                index.save()?;
+                println!(
+                    "Indexed {} directories under {} ({}) in {:.2?}",
+                    index.len(),
+                    root.path.display(),
+                    root.label,
+                    started.elapsed()
+                );
+            }
+            "status" => {
+                if i > 0 {
+                    println!();
+                }
+                match Index::load(&root.path, &root.options)? {
+                    Some(index) => {
+                        println!("Root:        {} ({})", index.root.display(), root.label);
+                        println!("Index file:  {}", index.path.display());
+                        println!("Directories: {}", index.len());
+                        println!("Updated:     {}", format_age(index.updated));
                     }
+                    None => println!(
+                        "No index for {} (run `tsh index rebuild` to create it)",
+                        root.path.display()
+                    ),
                 }
-            } else {
-                return Err(TshError::CommandFailed(format!(
-                    "find command for {}",
-                    search_dir
-                )));
             }
+            _ => {}
         }
+    }
 
-        if search_paths.is_empty() {
-            return Err(TshError::NoDirectoriesFound);
-        }
+    Ok(())
+}
 
-        println!("Searching in directories: {:?}", search_paths);
+fn run_import_command(matches: &ArgMatches) -> Result<(), TshError> {
+    let sources: Vec<import::Source> = match matches.get_many::<import::Source>("source") {
+        Some(sources) => sources.copied().collect(),
+        None => import::Source::all().to_vec(),
+    };
+    let file = matches.get_one::<PathBuf>("file");
 
-        return run_fd_with_fzf(&search_paths);
+    if file.is_some() && sources.len() != 1 {
+        return Err(TshError::CommandFailed(
+            "--file requires exactly one source".to_string(),
+        ));
     }
 
-    if let Some(dir) = custom_dir {
-        println!("Searching in custom directory: {:?}", dir);
-        run_fzf_in_directory(dir)
-    } else {
-        println!("Running default behavior (searching in home directory)...");
-        run_fzf_in_directory(&home_dir)
+    let mut history = History::load()?;
+    for source in sources {
+        let Some(path) = file.cloned().or_else(|| source.default_location()) else {
+            continue;
+        };
+        if !path.exists() {
+            println!("No {} database found at {}", source.name(), path.display());
+            continue;
+        }
+
+        match import::import(&mut history, source, &path) {
+            Ok(count) => println!(
+                "Imported {} directories from {} ({})",
+                count,
+                source.name(),
+                path.display()
+            ),
+            Err(e) => eprintln!("Failed to import {}: {}", source.name(), e),
+        }
     }
+    history.age();
+    history.save()
 }
 
-fn run_fd_with_fzf(search_paths: &[String]) -> Result<Option<PathBuf>, TshError> {
-    let find_cmd = if which("fd").is_ok() { "fd" } else { "find" };
-
-    let mut all_dirs = Vec::new();
-    for path in search_paths {
-        let args = if find_cmd == "fd" {
-            vec![".", path]
-        } else {
-            vec![path, "-type", "d"]
-        };
+fn format_age(updated: u64) -> String {
+    let secs = history::unix_now().saturating_sub(updated);
 
-        let output = ProcessCommand::new(find_cmd).args(&args).output()?;
+    match secs {
+        0..60 => format!("{}s ago", secs),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
 
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                if !line.is_empty() {
-                    all_dirs.push(line.to_string());
-                }
-            }
+fn find_and_select_directory(
+    directories: &[String],
+    config: &Config,
+    refreshes: &mut Vec<Refresh>,
+) -> Result<Option<(Action, Vec<Candidate>)>, TshError> {
+    let roots = config.search_roots()?;
+
+    if directories.is_empty() {
+        if *config.origin("roots") == Origin::Default {
+            println!("Running default behavior (searching in home directory)...");
         } else {
-            return Err(TshError::CommandFailed(format!(
-                "{} command for {}",
-                find_cmd, path
-            )));
+            let paths: Vec<&PathBuf> = roots.iter().map(|root| &root.path).collect();
+            println!("Searching in directories: {:?}", paths);
         }
     }
 
-    if all_dirs.is_empty() {
-        return Err(TshError::NoDirectoriesFound);
-    }
-
-    let mut fzf_cmd = ProcessCommand::new("fzf")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()?;
+    let positional = !directories.is_empty();
+    let sessions = if positional {
+        Vec::new()
+    } else {
+        tmux::list_sessions()
+    };
+    let running: HashSet<PathBuf> = sessions.iter().map(|s| s.path.clone()).collect();
+    let display = Display::new(config, &roots, running);
 
-    {
-        let fzf_stdin = fzf_cmd
-            .stdin
-            .as_mut()
-            .ok_or_else(|| TshError::CommandFailed("Failed to open fzf stdin".to_string()))?;
-        for dir in all_dirs {
-            fzf_stdin.write_all(format!("{}\n", dir).as_bytes())?;
+    let (tx, rx) = mpsc::channel();
+    if config.picker.sessions {
+        for session in sessions {
+            let _ = tx.send(Candidate::Session(session.name));
         }
     }
-
-    let output = fzf_cmd.wait_with_output()?;
-
-    if !output.status.success() {
-        return Ok(None);
+    // Lookups and --select-1/--exit-0 need every candidate, up to date;
+    // otherwise the picker opens right away and receives directories while
+    // scanning.
+    let streaming = !positional && !config.picker.select_1 && !config.picker.exit_0;
+    let producer = spawn_candidates(roots.clone(), config.sort, !streaming, tx);
+
+    if streaming {
+        let selected = picker::select(rx.into_iter(), display, config);
+        match producer.join() {
+            Ok(Ok(started)) => refreshes.extend(started),
+            Ok(Err(e)) => eprintln!("Failed to build directory index: {}", e),
+            Err(_) => {}
+        }
+        return selected;
     }
 
-    let selected = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if selected.is_empty() {
-        Ok(None)
-    } else {
-        Ok(Some(PathBuf::from(selected)))
+    let mut candidates = Vec::new();
+    let mut dirs = Vec::new();
+    for candidate in rx {
+        match candidate {
+            Candidate::Directory(dir) => dirs.push(dir),
+            session => candidates.push(session),
+        }
+    }
+    if let Ok(result) = producer.join() {
+        refreshes.extend(result?);
+    }
+    sort::sort_candidates(&mut dirs, config.sort);
+
+    if positional {
+        let mut seen = HashSet::new();
+        let mut matched = Vec::new();
+        for found in lookup::resolve(directories, &roots, &dirs)? {
+            match found.dirs.len() {
+                0 => eprintln!("No directory matches '{}'", found.pattern),
+                1 => {}
+                _ => lookup::report_ambiguous(&found),
+            }
+            matched.extend(
+                found
+                    .dirs
+                    .into_iter()
+                    .filter(|dir| seen.insert(dir.clone())),
+            );
+        }
+        dirs = matched;
+    }
+    candidates.extend(dirs.into_iter().map(Candidate::Directory));
+
+    match candidates.len() {
+        0 if positional || config.picker.exit_0 => Err(TshError::NoDirectoriesFound),
+        1 if positional || config.picker.select_1 => Ok(candidates
+            .pop()
+            .map(|candidate| (Action::Open, vec![candidate]))),
+        _ => picker::select(candidates.into_iter(), display, config),
     }
 }
 
-fn run_fzf_in_directory(dir: &Path) -> Result<Option<PathBuf>, TshError> {
-    let find_cmd = if which("fd").is_ok() { "fd" } else { "find" };
-
-    let dir_str = dir
-        .to_str()
-        .ok_or_else(|| TshError::CommandFailed("Invalid directory path".to_string()))?;
+// Roots served by the daemon or a cached index are sent sorted straight away;
+// the rest are streamed in discovery order while their index is built. Cached
+// indexes are refreshed first when `fresh` is set, otherwise in the background;
+// those refreshes are handed back to be joined once the session is open.
+fn spawn_candidates(
+    roots: Vec<SearchRoot>,
+    order: SortOrder,
+    fresh: bool,
+    tx: Sender<Candidate>,
+) -> JoinHandle<Result<Vec<Refresh>, TshError>> {
+    thread::spawn(move || {
+        let mut seen = HashSet::new();
+        let mut dirs = Vec::new();
+        let mut uncached = Vec::new();
+        let mut refreshes = Vec::new();
+
+        for root in roots {
+            if let Some(root_dirs) = daemon::query(&root.path, &root.options) {
+                dirs.extend(root_dirs);
+                continue;
+            }
+            match Index::load(&root.path, &root.options)? {
+                Some(mut index) if fresh => {
+                    index.refresh(&root.options);
+                    index.save()?;
+                    dirs.extend(index.directories(&root.options));
+                }
+                Some(mut index) => {
+                    dirs.extend(index.directories(&root.options));
+                    refreshes.push(thread::spawn(move || {
+                        index.refresh(&root.options);
+                        index.save()
+                    }));
+                }
+                None => uncached.push(root),
+            }
+        }
 
-    let args = if find_cmd == "fd" {
-        vec![".", "--type", "d", dir_str]
-    } else {
-        vec![dir_str, "-type", "d"]
-    };
+        dirs.retain(|dir| seen.insert(dir.clone()));
+        sort::sort_candidates(&mut dirs, order);
+        for dir in dirs {
+            let _ = tx.send(Candidate::Directory(dir));
+        }
 
-    let find_output = ProcessCommand::new(find_cmd).args(&args).output()?;
+        // Once the picker is closed nobody reads the rest of the walk, and a
+        // partial index must not be saved as if it were complete.
+        for root in &uncached {
+            let index = Index::stream(&root.path, &root.options, |dir| {
+                !seen.insert(dir.clone()) || tx.send(Candidate::Directory(dir)).is_ok()
+            })?;
+            match index {
+                Some(index) => index.save()?,
+                None => break,
+            }
+        }
+        Ok(refreshes)
+    })
+}
 
-    if !find_output.status.success() {
-        return Err(TshError::CommandFailed(format!(
-            "Failed to execute {} command",
-            find_cmd
-        )));
+fn create_tmux_session(dir: &Path, config: &Config) -> Result<(), TshError> {
+    if let Err(e) = history::record_visit(dir) {
+        eprintln!("Failed to record visit to {}: {}", dir.display(), e);
     }
 
-    let exclude_pattern = Regex::new(r"/node_modules/|/\.git/|/\.cache/|/tmp/|/Library/")
-        .map_err(|e| TshError::CommandFailed(format!("Failed to compile regex: {}", e)))?;
+    let session_name = layout::session_name(dir, &config.session).ok_or_else(|| {
+        TshError::CommandFailed("Could not extract session name from directory".to_string())
+    })?;
 
-    let dirs: Vec<String> = String::from_utf8_lossy(&find_output.stdout)
-        .lines()
-        .filter(|line| !line.is_empty() && !exclude_pattern.is_match(line))
-        .map(|s| s.to_string())
-        .collect();
+    let in_tmux = env::var("TMUX").is_ok();
 
-    if dirs.is_empty() {
-        return Err(TshError::NoDirectoriesFound);
-    }
+    if tmux::has_session(&session_name)? {
+        println!("Session '{}' already exists, attaching...", session_name);
+        attach_tmux_session(&session_name)?;
+    } else {
+        println!("Creating new session '{}'...", session_name);
 
-    let mut fzf_cmd = ProcessCommand::new("fzf")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()?;
+        let layout = layout::resolve(dir, config)?;
+        report_layout(dir, layout.as_ref(), config);
+        if in_tmux || layout.is_some() {
+            new_session(&session_name, dir, layout)?;
+            attach_tmux_session(&session_name)?;
+        } else {
+            let dir_str = dir.to_string_lossy();
+            let status = ProcessCommand::new("tmux")
+                .args(["new-session", "-A", "-s", &session_name, "-c", &dir_str])
+                .status()?;
 
-    {
-        let fzf_stdin = fzf_cmd
-            .stdin
-            .as_mut()
-            .ok_or_else(|| TshError::CommandFailed("Failed to open fzf stdin".to_string()))?;
-        for dir in dirs {
-            fzf_stdin.write_all(format!("{}\n", dir).as_bytes())?;
+            if !status.success() {
+                return Err(TshError::CommandFailed(
+                    "Failed to create and attach to tmux session".to_string(),
+                ));
+            }
         }
     }
 
-    let output = fzf_cmd.wait_with_output()?;
+    Ok(())
+}
 
-    if !output.status.success() {
-        return Ok(None);
+fn new_session(name: &str, dir: &Path, layout: Option<Layout>) -> Result<(), TshError> {
+    match layout {
+        Some(mut layout) => {
+            layout::approve(&mut layout)?;
+            println!("Applying {}...", layout.source);
+            layout::create_session(name, dir, &layout.template)
+        }
+        None => tmux::new_detached_session(name, dir),
     }
+}
 
-    let selected = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if selected.is_empty() {
-        Ok(None)
-    } else {
-        Ok(Some(PathBuf::from(selected)))
+fn report_layout(dir: &Path, layout: Option<&Layout>, config: &Config) {
+    if !config.verbose {
+        return;
+    }
+    match project::detect(dir, &config.project_types) {
+        Some(detected) => println!("Detected project type: {}", detected),
+        None => println!("Detected project type: none"),
+    }
+    if layout.is_none() {
+        println!("No layout applies to {}", dir.display());
     }
 }
 
-fn create_tmux_session(dir: &Path) -> Result<(), TshError> {
-    let session_name = dir
-        .file_name()
-        .and_then(|n| n.to_str())
-        .ok_or_else(|| {
-            TshError::CommandFailed("Could not extract session name from directory".to_string())
-        })?
-        .replace(".", "_");
-
-    let in_tmux = env::var("TMUX").is_ok();
+fn attach_tmux_session(session_name: &str) -> Result<(), TshError> {
+    if env::var("TMUX").is_ok() {
+        let status = ProcessCommand::new("tmux")
+            .args(["switch-client", "-t", &tmux::session_target(session_name)])
+            .status()?;
 
-    let output = ProcessCommand::new("tmux")
-        .args(&["has-session", "-t", &session_name])
-        .output()?;
+        if !status.success() {
+            return Err(TshError::CommandFailed(
+                "Failed to switch tmux client".to_string(),
+            ));
+        }
+    } else {
+        let status = ProcessCommand::new("tmux")
+            .args(["attach-session", "-t", &tmux::session_target(session_name)])
+            .status()?;
+
+        if !status.success() {
+            return Err(TshError::CommandFailed(
+                "Failed to attach to tmux session".to_string(),
+            ));
+        }
+    }
 
-    let has_session = output.status.success();
+    Ok(())
+}
 
-    if has_session {
-        println!("Session '{}' already exists, attaching...", session_name);
+fn open_candidates(candidates: &[Candidate], config: &Config) -> Result<(), TshError> {
+    match candidates {
+        [Candidate::Directory(dir)] => create_tmux_session(dir, config),
+        [Candidate::Session(name)] => {
+            println!("Switching to session '{}'...", name);
+            attach_tmux_session(name)
+        }
+        _ => match start_sessions(candidates, config)?.last() {
+            Some(name) => {
+                println!("Switching to session '{}'...", name);
+                attach_tmux_session(name)
+            }
+            None => Ok(()),
+        },
+    }
+}
 
-        if in_tmux {
-            let status = ProcessCommand::new("tmux")
-                .args(&["switch-client", "-t", &session_name])
-                .status()?;
+fn start_sessions(candidates: &[Candidate], config: &Config) -> Result<Vec<String>, TshError> {
+    let mut names = Vec::new();
+    let mut created = Vec::new();
+    let mut existing = Vec::new();
 
-            if !status.success() {
-                return Err(TshError::CommandFailed(
-                    "Failed to switch tmux client".to_string(),
-                ));
-            }
+    for candidate in candidates {
+        let (name, new) = match candidate {
+            Candidate::Session(name) => (name.clone(), false),
+            Candidate::Directory(dir) => start_session(dir, config)?,
+        };
+        if new {
+            created.push(name.clone());
         } else {
-            let status = ProcessCommand::new("tmux")
-                .args(&["attach-session", "-t", &session_name])
-                .status()?;
-
-            if !status.success() {
-                return Err(TshError::CommandFailed(
-                    "Failed to attach to tmux session".to_string(),
-                ));
-            }
+            existing.push(name.clone());
         }
-    } else {
-        println!("Creating new session '{}'...", session_name);
+        names.push(name);
+    }
 
-        let dir_str = dir.to_string_lossy();
+    if !created.is_empty() {
+        println!("Created sessions: {}", created.join(", "));
+    }
+    if !existing.is_empty() {
+        println!("Already running: {}", existing.join(", "));
+    }
+    Ok(names)
+}
 
-        if in_tmux {
-            let create_status = ProcessCommand::new("tmux")
-                .args(&["new-session", "-d", "-s", &session_name, "-c", &dir_str])
-                .status()?;
+fn start_session(dir: &Path, config: &Config) -> Result<(String, bool), TshError> {
+    if let Err(e) = history::record_visit(dir) {
+        eprintln!("Failed to record visit to {}: {}", dir.display(), e);
+    }
 
-            if !create_status.success() {
-                return Err(TshError::CommandFailed(
-                    "Failed to create tmux session".to_string(),
-                ));
-            }
+    let session_name = layout::session_name(dir, &config.session).ok_or_else(|| {
+        TshError::CommandFailed("Could not extract session name from directory".to_string())
+    })?;
 
-            let switch_status = ProcessCommand::new("tmux")
-                .args(&["switch-client", "-t", &session_name])
-                .status()?;
+    if tmux::has_session(&session_name)? {
+        return Ok((session_name, false));
+    }
+    let layout = layout::resolve(dir, config)?;
+    report_layout(dir, layout.as_ref(), config);
+    new_session(&session_name, dir, layout)?;
+    Ok((session_name, true))
+}
 
-            if !switch_status.success() {
-                return Err(TshError::CommandFailed(
-                    "Failed to switch tmux client".to_string(),
-                ));
-            }
-        } else {
-            let status = ProcessCommand::new("tmux")
-                .args(&["new-session", "-A", "-s", &session_name, "-c", &dir_str])
-                .status()?;
+fn open_tmux_window(candidate: &Candidate, config: &Config) -> Result<(), TshError> {
+    if env::var("TMUX").is_err() {
+        return Err(TshError::CommandFailed(
+            "Opening a new window requires running inside tmux".to_string(),
+        ));
+    }
 
-            if !status.success() {
-                return Err(TshError::CommandFailed(
-                    "Failed to create and attach to tmux session".to_string(),
-                ));
-            }
-        }
+    let dir = match candidate {
+        Candidate::Directory(dir) => dir.clone(),
+        Candidate::Session(name) => tmux::list_sessions()
+            .into_iter()
+            .find(|session| &session.name == name)
+            .map(|session| session.path)
+            .ok_or_else(|| TshError::CommandFailed(format!("Session '{}' not found", name)))?,
+    };
+    if let Err(e) = history::record_visit(&dir) {
+        eprintln!("Failed to record visit to {}: {}", dir.display(), e);
+    }
+
+    let window_name = layout::session_name(&dir, &config.session).ok_or_else(|| {
+        TshError::CommandFailed("Could not extract window name from directory".to_string())
+    })?;
+    println!("Opening window '{}'...", window_name);
+    tmux::new_window(&window_name, &dir)
+}
+
+fn running_session(candidate: &Candidate, config: &Config) -> Result<Option<String>, TshError> {
+    match candidate {
+        Candidate::Session(name) => Ok(Some(name.clone())),
+        Candidate::Directory(dir) => match layout::session_name(dir, &config.session) {
+            Some(name) if tmux::has_session(&name)? => Ok(Some(name)),
+            _ => Ok(None),
+        },
     }
+}
+
+fn rename_tmux_session(session_name: &str) -> Result<(), TshError> {
+    eprint!("Rename session '{}' to: ", session_name);
+    io::stderr().flush()?;
 
+    let mut new_name = String::new();
+    io::stdin().read_line(&mut new_name)?;
+    let new_name = new_name.trim();
+
+    if new_name.is_empty() || new_name == session_name {
+        return Ok(());
+    }
+    tmux::rename_session(session_name, new_name)?;
+    println!("Renamed session '{}' to '{}'", session_name, new_name);
     Ok(())
 }
@@ -0,0 +1,404 @@
+use crate::TshError;
+use crate::builtin;
+use crate::candidate::{self, Candidate, FIELD_SEPARATOR};
+use crate::config::Config;
+use crate::display::Display;
+use crate::git::{self, Status};
+use crate::preview;
+use crate::sort::SortOrder;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command as ProcessCommand, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use which::which;
+
+const PROMPT: &str = "tsh";
+const CUSTOM_KEY_EXIT: i32 = 10;
+pub const ACTION_HELP: &str =
+    "enter: open  tab: mark  ctrl-o: detached  ctrl-w: new window  ctrl-r: rename  ctrl-x: kill";
+
+// Picker-independent actions with their fzf/sk and rofi key names.
+const ACTION_KEYS: &[(Action, &str, &str)] = &[
+    (Action::Kill, "ctrl-x", "Control+x"),
+    (Action::Rename, "ctrl-r", "Control+r"),
+    (Action::NewWindow, "ctrl-w", "Control+w"),
+    (Action::Detached, "ctrl-o", "Control+o"),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Auto,
+    Fzf,
+    Sk,
+    Gum,
+    Rofi,
+    Dmenu,
+    Fuzzel,
+    Wofi,
+    Builtin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Open,
+    Kill,
+    Rename,
+    NewWindow,
+    Detached,
+}
+
+pub type Selection = (Action, Vec<String>);
+
+pub trait Picker {
+    fn command(&self) -> Option<&'static str>;
+
+    // Lines keep arriving until the sender is dropped, so pickers must not
+    // wait for the full list before showing it.
+    fn pick(&self, lines: Receiver<String>, config: &Config)
+    -> Result<Option<Selection>, TshError>;
+}
+
+struct Fzf;
+struct Skim;
+struct Gum;
+struct Builtin;
+
+struct Launcher {
+    program: &'static str,
+    args: &'static [&'static str],
+    custom_keys: bool,
+    columns: bool,
+}
+
+impl Backend {
+    // Terminal finders are preferred when tsh runs in a terminal; without one
+    // (e.g. bound to a window-manager hotkey) a graphical launcher is used.
+    pub fn resolve(self) -> Backend {
+        if self != Backend::Auto {
+            return self;
+        }
+
+        let mut preferred = Vec::new();
+        if !io::stdin().is_terminal() {
+            if env::var_os("WAYLAND_DISPLAY").is_some() {
+                preferred.extend([Backend::Fuzzel, Backend::Wofi, Backend::Rofi]);
+            }
+            if env::var_os("DISPLAY").is_some() {
+                preferred.extend([Backend::Rofi, Backend::Dmenu]);
+            }
+        }
+        preferred.extend([Backend::Fzf, Backend::Sk]);
+
+        preferred
+            .into_iter()
+            .find(|backend| {
+                backend
+                    .picker()
+                    .command()
+                    .is_some_and(|cmd| which(cmd).is_ok())
+            })
+            .unwrap_or(Backend::Builtin)
+    }
+
+    pub fn picker(self) -> Box<dyn Picker> {
+        match self {
+            Backend::Fzf => Box::new(Fzf),
+            Backend::Sk => Box::new(Skim),
+            Backend::Gum => Box::new(Gum),
+            Backend::Rofi => Box::new(Launcher {
+                program: "rofi",
+                args: &[
+                    "-dmenu",
+                    "-i",
+                    "-p",
+                    PROMPT,
+                    "-display-columns",
+                    "1",
+                    "-display-column-separator",
+                    "\t",
+                ],
+                custom_keys: true,
+                columns: true,
+            }),
+            Backend::Dmenu => Box::new(Launcher {
+                program: "dmenu",
+                args: &["-i", "-l", "20", "-p", PROMPT],
+                custom_keys: false,
+                columns: false,
+            }),
+            Backend::Fuzzel => Box::new(Launcher {
+                program: "fuzzel",
+                args: &["--dmenu", "--prompt", "tsh> "],
+                custom_keys: false,
+                columns: false,
+            }),
+            Backend::Wofi => Box::new(Launcher {
+                program: "wofi",
+                args: &["--dmenu", "--insensitive", "--prompt", PROMPT],
+                custom_keys: false,
+                columns: false,
+            }),
+            Backend::Auto | Backend::Builtin => Box::new(Builtin),
+        }
+    }
+}
+
+impl Picker for Fzf {
+    fn command(&self) -> Option<&'static str> {
+        Some("fzf")
+    }
+
+    fn pick(
+        &self,
+        lines: Receiver<String>,
+        config: &Config,
+    ) -> Result<Option<Selection>, TshError> {
+        run_finder("fzf", lines, config)
+    }
+}
+
+impl Picker for Skim {
+    fn command(&self) -> Option<&'static str> {
+        Some("sk")
+    }
+
+    fn pick(
+        &self,
+        lines: Receiver<String>,
+        config: &Config,
+    ) -> Result<Option<Selection>, TshError> {
+        run_finder("sk", lines, config)
+    }
+}
+
+impl Picker for Gum {
+    fn command(&self) -> Option<&'static str> {
+        Some("gum")
+    }
+
+    fn pick(
+        &self,
+        lines: Receiver<String>,
+        config: &Config,
+    ) -> Result<Option<Selection>, TshError> {
+        let mut cmd = ProcessCommand::new("gum");
+        cmd.args([
+            "filter",
+            "--no-limit",
+            "--placeholder",
+            "Select a directory",
+        ]);
+        cmd.args(&config.picker.args);
+        let (code, stdout) = run(cmd, lines, false)?;
+        if code != 0 {
+            return Ok(None);
+        }
+        Ok(selection(Action::Open, stdout.lines()))
+    }
+}
+
+impl Picker for Launcher {
+    fn command(&self) -> Option<&'static str> {
+        Some(self.program)
+    }
+
+    fn pick(
+        &self,
+        lines: Receiver<String>,
+        config: &Config,
+    ) -> Result<Option<Selection>, TshError> {
+        let mut cmd = ProcessCommand::new(self.program);
+        cmd.args(self.args);
+        if self.custom_keys {
+            cmd.args(["-multi-select", "-kb-clear-line", ""]);
+            for (i, (_, _, key)) in ACTION_KEYS.iter().enumerate() {
+                cmd.arg(format!("-kb-custom-{}", i + 1)).arg(key);
+            }
+        }
+        cmd.args(&config.picker.args);
+
+        let (code, stdout) = run(cmd, lines, self.columns)?;
+        let action = if code == 0 {
+            Action::Open
+        } else {
+            match usize::try_from(code - CUSTOM_KEY_EXIT)
+                .ok()
+                .and_then(|i| ACTION_KEYS.get(i))
+            {
+                Some(&(action, _, _)) => action,
+                None => return Ok(None),
+            }
+        };
+        Ok(selection(action, stdout.lines()))
+    }
+}
+
+impl Picker for Builtin {
+    fn command(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn pick(
+        &self,
+        lines: Receiver<String>,
+        _config: &Config,
+    ) -> Result<Option<Selection>, TshError> {
+        builtin::select(lines)
+    }
+}
+
+impl Action {
+    pub fn from_key(key: &str) -> Action {
+        ACTION_KEYS
+            .iter()
+            .find(|(_, name, _)| *name == key)
+            .map_or(Action::Open, |&(action, _, _)| action)
+    }
+}
+
+pub fn select(
+    candidates: impl Iterator<Item = Candidate> + Send + 'static,
+    display: Display,
+    config: &Config,
+) -> Result<Option<(Action, Vec<Candidate>)>, TshError> {
+    let budget = config
+        .picker
+        .git_status
+        .then(|| Duration::from_millis(config.picker.git_budget_ms));
+    let (tx, lines) = mpsc::channel();
+    thread::spawn(move || {
+        let annotated: Box<dyn Iterator<Item = (Candidate, Option<Status>)>> = match budget {
+            Some(budget) => Box::new(git::annotate(candidates, budget)),
+            None => Box::new(candidates.map(|candidate| (candidate, None))),
+        };
+        for (candidate, status) in annotated {
+            if tx.send(display.line(&candidate, status.as_ref())).is_err() {
+                break;
+            }
+        }
+    });
+    let selected = config
+        .picker
+        .backend
+        .resolve()
+        .picker()
+        .pick(lines, config)?;
+    Ok(selected.map(|(action, lines)| {
+        (
+            action,
+            lines.iter().map(|line| Candidate::parse(line)).collect(),
+        )
+    }))
+}
+
+fn selection<'a>(action: Action, lines: impl Iterator<Item = &'a str>) -> Option<Selection> {
+    let lines: Vec<String> = lines
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    (!lines.is_empty()).then_some((action, lines))
+}
+
+// fzf and sk share their flags; with --expect the first output line names the
+// key that ended the selection and is empty for enter.
+fn run_finder(
+    program: &str,
+    lines: Receiver<String>,
+    config: &Config,
+) -> Result<Option<Selection>, TshError> {
+    let mut cmd = ProcessCommand::new(program);
+    if config.sort != SortOrder::Alpha {
+        cmd.arg("--tiebreak=index");
+    }
+    if config.picker.preview
+        && let Some(preview) = preview::command(config)
+    {
+        cmd.arg("--preview").arg(preview);
+        cmd.envs(config.child_env());
+    }
+    let keys: Vec<&str> = ACTION_KEYS.iter().map(|(_, key, _)| *key).collect();
+    cmd.arg("--multi");
+    cmd.arg(format!("--delimiter={}", FIELD_SEPARATOR));
+    cmd.arg("--with-nth=1");
+    cmd.arg(format!("--expect={}", keys.join(",")));
+    cmd.arg("--header").arg(ACTION_HELP);
+    cmd.args(&config.picker.args);
+
+    let (code, stdout) = run(cmd, lines, true)?;
+    if code != 0 {
+        return Ok(None);
+    }
+    let mut output = stdout.lines();
+    let action = Action::from_key(output.next().unwrap_or_default());
+    Ok(selection(action, output))
+}
+
+// Pickers without `columns` support are only given the display text; their
+// output is mapped back to the full lines afterwards.
+fn run(
+    mut cmd: ProcessCommand,
+    lines: Receiver<String>,
+    columns: bool,
+) -> Result<(i32, String), TshError> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+
+    // The writer stops on its own once the picker exits and closes the pipe.
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| TshError::CommandFailed(format!("Failed to open {} stdin", program)))?;
+    let shown = Arc::new(Mutex::new(HashMap::new()));
+    {
+        let shown = Arc::clone(&shown);
+        thread::spawn(move || {
+            for line in lines {
+                let written = if columns {
+                    writeln!(stdin, "{}", line)
+                } else {
+                    let Ok(mut shown) = shown.lock() else {
+                        break;
+                    };
+                    // Two candidates can look alike, e.g. directories under
+                    // roots sharing a label; the later one is told apart by
+                    // its key so that both can still be selected.
+                    let mut text = candidate::display_text(&line).to_string();
+                    if shown.contains_key(text.trim()) {
+                        text = format!("{}  {}", text, Candidate::parse(&line).key());
+                    }
+                    let written = writeln!(stdin, "{}", text);
+                    shown.insert(text.trim().to_string(), line);
+                    written
+                };
+                if written.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let output = child.wait_with_output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stdout = if columns {
+        stdout
+    } else {
+        let shown = shown.lock().map_err(|_| {
+            TshError::CommandFailed(format!("Failed to read {} selection", program))
+        })?;
+        stdout
+            .lines()
+            .map(|line| shown.get(line.trim()).map_or(line, String::as_str))
+            .map(|line| format!("{}\n", line))
+            .collect()
+    };
+    Ok((output.status.code().unwrap_or(-1), stdout))
+}
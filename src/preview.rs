@@ -0,0 +1,211 @@
+use crate::TshError;
+use crate::candidate::Candidate;
+use crate::config::{Config, Origin};
+use crate::layout;
+use crate::project;
+use crate::tmux;
+use std::env;
+use std::fs::{self, DirEntry};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command as ProcessCommand, Stdio};
+
+const TREE_DEPTH: usize = 2;
+const TREE_ENTRIES: usize = 40;
+const STATUS_LINES: usize = 10;
+const RECENT_COMMITS: &str = "5";
+const README_LINES: usize = 20;
+
+// Shell command handed to fzf/sk; `{}` is replaced by the quoted candidate.
+// The rest of the configuration reaches it through `Config::child_env`, but a
+// template only outranks the project file when given as a flag.
+pub fn command(config: &Config) -> Option<String> {
+    let exe = env::current_exe().ok()?;
+    let mut command = quote(exe.to_str()?);
+    if let Origin::Cli(_) = config.origin("session.template")
+        && let Some(template) = &config.session.template
+    {
+        command.push_str(" --template ");
+        command.push_str(&quote(template));
+    }
+    command.push_str(" preview {}");
+    Some(command)
+}
+
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+pub fn run(candidate: &Candidate, config: &Config) -> Result<(), TshError> {
+    let mut out = io::stdout().lock();
+
+    let dir = match candidate {
+        Candidate::Session(name) => {
+            session(&mut out, name)?;
+            match tmux::list_sessions().into_iter().find(|s| &s.name == name) {
+                Some(session) => session.path,
+                None => return Ok(()),
+            }
+        }
+        Candidate::Directory(dir) => {
+            if let Some(name) = layout::session_name(dir, &config.session)
+                && tmux::has_session(&name).unwrap_or(false)
+            {
+                session(&mut out, &name)?;
+            }
+            dir.clone()
+        }
+    };
+    let dir = dir.as_path();
+
+    writeln!(out, "\x1b[1;34m{}\x1b[0m", dir.display())?;
+    let mut remaining = TREE_ENTRIES;
+    tree(&mut out, dir, "", 1, &mut remaining)?;
+
+    section(&mut out, "Project")?;
+    match project::detect(dir, &config.project_types) {
+        Some(detected) => writeln!(out, "Type:   {}", detected)?,
+        None => writeln!(out, "Type:   unknown")?,
+    }
+    match layout::resolve(dir, config) {
+        Ok(Some(layout)) => writeln!(out, "Layout: {}", layout.source)?,
+        Ok(None) => writeln!(out, "Layout: none")?,
+        Err(e) => writeln!(out, "Layout: {}", e)?,
+    }
+
+    if let Some(status) = git(
+        dir,
+        &["-c", "color.status=always", "status", "--short", "--branch"],
+    ) {
+        section(&mut out, "Git")?;
+        let lines: Vec<&str> = status.lines().collect();
+        for line in lines.iter().take(STATUS_LINES + 1) {
+            writeln!(out, "{}", line)?;
+        }
+        if lines.len() > STATUS_LINES + 1 {
+            writeln!(out, "… {} more changes", lines.len() - STATUS_LINES - 1)?;
+        }
+
+        if let Some(log) = git(
+            dir,
+            &[
+                "log",
+                "--color=always",
+                "--format=%C(yellow)%h%C(reset) %s %C(dim)(%cr)%C(reset)",
+                "-n",
+                RECENT_COMMITS,
+            ],
+        ) && !log.trim().is_empty()
+        {
+            section(&mut out, "Recent commits")?;
+            write!(out, "{}", log)?;
+        }
+    }
+
+    if let Some(readme) = find_readme(dir)
+        && let Ok(contents) = fs::read_to_string(&readme)
+    {
+        let name = readme.file_name().unwrap_or_default().to_string_lossy();
+        section(&mut out, &name)?;
+        for line in contents.lines().take(README_LINES) {
+            writeln!(out, "{}", line)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn session(out: &mut impl Write, name: &str) -> io::Result<()> {
+    writeln!(out, "\x1b[1;32mtmux session '{}'\x1b[0m", name)?;
+    if let Some(windows) = tmux::list_windows(name) {
+        write!(out, "{}", windows)?;
+    }
+    if let Some(pane) = tmux::capture_pane(name) {
+        section(out, "Active pane")?;
+        writeln!(out, "{}\x1b[0m", pane.trim_end())?;
+    }
+    writeln!(out)
+}
+
+fn section(out: &mut impl Write, title: &str) -> io::Result<()> {
+    writeln!(out, "\n\x1b[1m── {} ──\x1b[0m", title)
+}
+
+fn tree(
+    out: &mut impl Write,
+    dir: &Path,
+    prefix: &str,
+    depth: usize,
+    remaining: &mut usize,
+) -> io::Result<bool> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(true);
+    };
+    let mut entries: Vec<DirEntry> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name() != ".git")
+        .collect();
+    entries.sort_by_key(|entry| (!is_dir(entry), entry.file_name()));
+
+    let count = entries.len();
+    for (i, entry) in entries.into_iter().enumerate() {
+        if *remaining == 0 {
+            writeln!(out, "{}…", prefix)?;
+            return Ok(false);
+        }
+        *remaining -= 1;
+
+        let last = i + 1 == count;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let branch = if last { "└── " } else { "├── " };
+        if is_dir(&entry) {
+            writeln!(out, "{}{}\x1b[34m{}/\x1b[0m", prefix, branch, name)?;
+            if depth < TREE_DEPTH {
+                let nested = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                if !tree(out, &entry.path(), &nested, depth + 1, remaining)? {
+                    return Ok(false);
+                }
+            }
+        } else {
+            writeln!(out, "{}{}{}", prefix, branch, name)?;
+        }
+    }
+
+    Ok(true)
+}
+
+fn is_dir(entry: &DirEntry) -> bool {
+    entry.file_type().is_ok_and(|t| t.is_dir())
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = ProcessCommand::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn find_readme(dir: &Path) -> Option<PathBuf> {
+    let mut candidates: Vec<_> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .to_ascii_lowercase()
+                .starts_with("readme")
+                && entry.file_type().is_ok_and(|t| t.is_file())
+        })
+        .map(|entry| entry.path())
+        .collect();
+    candidates.sort();
+    candidates.into_iter().next()
+}
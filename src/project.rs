@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+// Rules are tried in order and the first one with a marker present wins, so
+// language-specific markers come before generic ones like Makefile.
+const DEFAULT_PROJECT_TYPES: &[(&str, &[&str])] = &[
+    ("rust", &["Cargo.toml"]),
+    ("node", &["package.json"]),
+    ("go", &["go.mod"]),
+    (
+        "python",
+        &["pyproject.toml", "setup.py", "requirements.txt"],
+    ),
+    ("ruby", &["Gemfile"]),
+    ("java", &["pom.xml", "build.gradle", "build.gradle.kts"]),
+    ("nix", &["flake.nix"]),
+    ("make", &["Makefile"]),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectType {
+    pub name: String,
+    pub markers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+pub struct Detection<'a> {
+    pub kind: &'a ProjectType,
+    pub marker: &'a str,
+}
+
+impl ProjectType {
+    pub fn defaults() -> Vec<ProjectType> {
+        DEFAULT_PROJECT_TYPES
+            .iter()
+            .map(|(name, markers)| ProjectType {
+                name: name.to_string(),
+                markers: markers.iter().map(|m| m.to_string()).collect(),
+                template: None,
+            })
+            .collect()
+    }
+
+    // Without an explicit template the rule selects the template of the same
+    // name, if one is configured.
+    pub fn template_name(&self) -> &str {
+        self.template.as_deref().unwrap_or(&self.name)
+    }
+}
+
+impl fmt::Display for Detection<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.kind.name, self.marker)
+    }
+}
+
+pub fn detect<'a>(dir: &Path, rules: &'a [ProjectType]) -> Option<Detection<'a>> {
+    rules.iter().find_map(|kind| {
+        kind.markers
+            .iter()
+            .find(|marker| dir.join(marker).symlink_metadata().is_ok())
+            .map(|marker| Detection {
+                kind,
+                marker: marker.as_str(),
+            })
+    })
+}
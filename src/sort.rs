@@ -0,0 +1,42 @@
+use crate::history::{self, History};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Frecency,
+    Alpha,
+    Mtime,
+}
+
+pub fn sort_candidates(dirs: &mut [PathBuf], order: SortOrder) {
+    match order {
+        SortOrder::Alpha => dirs.sort(),
+        SortOrder::Mtime => {
+            dirs.sort_by_cached_key(|dir| {
+                Reverse(
+                    fs::metadata(dir)
+                        .and_then(|meta| meta.modified())
+                        .unwrap_or(SystemTime::UNIX_EPOCH),
+                )
+            });
+        }
+        SortOrder::Frecency => {
+            let history = match History::load() {
+                Ok(history) => history,
+                Err(e) => {
+                    eprintln!("Failed to load history: {}", e);
+                    return;
+                }
+            };
+            let now = history::unix_now();
+            let score = |dir: &PathBuf| history.get(dir).map_or(0.0, |visit| visit.frecency(now));
+            dirs.sort_by(|a, b| score(b).total_cmp(&score(a)));
+        }
+    }
+}
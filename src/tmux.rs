@@ -0,0 +1,228 @@
+use crate::TshError;
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+use std::process::{Command as ProcessCommand, Stdio};
+
+const SESSION_FORMAT: &str = "#{session_name}\t#{session_last_attached}\t#{session_path}";
+const PANE_FORMAT: &str = "#{pane_id}";
+const WINDOW_FORMAT: &str =
+    "#{window_index}: #{window_name}#{?window_active, (active),} [#{window_panes} panes]";
+
+pub struct Session {
+    pub name: String,
+    pub path: PathBuf,
+    pub last_attached: u64,
+}
+
+pub fn list_sessions() -> Vec<Session> {
+    let Some(output) = query(&["list-sessions", "-F", SESSION_FORMAT]) else {
+        return Vec::new();
+    };
+
+    let mut sessions: Vec<Session> = output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let name = fields.next()?.to_string();
+            let last_attached = fields.next()?.parse().unwrap_or(0);
+            let path = PathBuf::from(fields.next()?);
+            Some(Session {
+                name,
+                path,
+                last_attached,
+            })
+        })
+        .collect();
+    sessions.sort_by_key(|session| Reverse(session.last_attached));
+    sessions
+}
+
+// A bare name also matches sessions it is a prefix of (or an fnmatch pattern
+// for), so "api" could otherwise resolve to "api-gateway".
+pub fn session_target(name: &str) -> String {
+    format!("={}", name)
+}
+
+pub fn has_session(name: &str) -> Result<bool, TshError> {
+    let output = ProcessCommand::new("tmux")
+        .args(["has-session", "-t", &session_target(name)])
+        .output()?;
+    Ok(output.status.success())
+}
+
+pub fn new_detached_session(name: &str, dir: &Path) -> Result<(), TshError> {
+    let dir = dir.to_string_lossy();
+    run(
+        &["new-session", "-d", "-s", name, "-c", &dir],
+        "Failed to create tmux session",
+    )
+}
+
+// The layout helpers below return the id of the created pane so that later
+// commands can target it regardless of base-index settings.
+pub fn new_session_pane(
+    session: &str,
+    window: Option<&str>,
+    dir: &Path,
+    env: &[String],
+) -> Result<String, TshError> {
+    let dir = dir.to_string_lossy();
+    let mut args = vec![
+        "new-session",
+        "-d",
+        "-P",
+        "-F",
+        PANE_FORMAT,
+        "-s",
+        session,
+        "-c",
+        &dir,
+    ];
+    if let Some(window) = window {
+        args.extend(["-n", window]);
+    }
+    for var in env {
+        args.extend(["-e", var]);
+    }
+    output(&args, "Failed to create tmux session")
+}
+
+pub fn new_window_pane(
+    session: &str,
+    window: Option<&str>,
+    dir: &Path,
+    env: &[String],
+) -> Result<String, TshError> {
+    let dir = dir.to_string_lossy();
+    let target = format!("{}:", session_target(session));
+    let mut args = vec![
+        "new-window",
+        "-d",
+        "-P",
+        "-F",
+        PANE_FORMAT,
+        "-t",
+        &target,
+        "-c",
+        &dir,
+    ];
+    if let Some(window) = window {
+        args.extend(["-n", window]);
+    }
+    for var in env {
+        args.extend(["-e", var]);
+    }
+    output(&args, "Failed to create tmux window")
+}
+
+pub fn split_pane(
+    pane: &str,
+    horizontal: bool,
+    size: Option<&str>,
+    dir: &Path,
+    env: &[String],
+) -> Result<String, TshError> {
+    let dir = dir.to_string_lossy();
+    let mut args = vec![
+        "split-window",
+        "-d",
+        if horizontal { "-h" } else { "-v" },
+        "-P",
+        "-F",
+        PANE_FORMAT,
+        "-t",
+        pane,
+        "-c",
+        &dir,
+    ];
+    if let Some(size) = size {
+        args.extend(["-l", size]);
+    }
+    for var in env {
+        args.extend(["-e", var]);
+    }
+    output(&args, "Failed to split tmux pane")
+}
+
+pub fn send_keys(pane: &str, command: &str) -> Result<(), TshError> {
+    run(
+        &["send-keys", "-t", pane, command, "Enter"],
+        "Failed to send keys to tmux pane",
+    )
+}
+
+pub fn select_layout(pane: &str, layout: &str) -> Result<(), TshError> {
+    run(
+        &["select-layout", "-t", pane, layout],
+        "Failed to apply tmux layout",
+    )
+}
+
+pub fn select_pane(pane: &str) -> Result<(), TshError> {
+    run(&["select-pane", "-t", pane], "Failed to select tmux pane")
+}
+
+pub fn new_window(name: &str, dir: &Path) -> Result<(), TshError> {
+    let dir = dir.to_string_lossy();
+    run(
+        &["new-window", "-n", name, "-c", &dir],
+        "Failed to create tmux window",
+    )
+}
+
+pub fn kill_session(name: &str) -> Result<(), TshError> {
+    run(
+        &["kill-session", "-t", &session_target(name)],
+        "Failed to kill tmux session",
+    )
+}
+
+pub fn rename_session(name: &str, new_name: &str) -> Result<(), TshError> {
+    run(
+        &["rename-session", "-t", &session_target(name), new_name],
+        "Failed to rename tmux session",
+    )
+}
+
+pub fn list_windows(name: &str) -> Option<String> {
+    query(&[
+        "list-windows",
+        "-t",
+        &session_target(name),
+        "-F",
+        WINDOW_FORMAT,
+    ])
+}
+
+pub fn capture_pane(name: &str) -> Option<String> {
+    let target = format!("{}:", session_target(name));
+    query(&["capture-pane", "-p", "-e", "-t", &target])
+}
+
+fn run(args: &[&str], failure: &str) -> Result<(), TshError> {
+    let status = ProcessCommand::new("tmux").args(args).status()?;
+    if !status.success() {
+        return Err(TshError::CommandFailed(failure.to_string()));
+    }
+    Ok(())
+}
+
+fn output(args: &[&str], failure: &str) -> Result<String, TshError> {
+    let output = ProcessCommand::new("tmux").args(args).output()?;
+    if !output.status.success() {
+        return Err(TshError::CommandFailed(failure.to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn query(args: &[&str]) -> Option<String> {
+    let output = ProcessCommand::new("tmux")
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,133 @@
+use crate::filter::PatternSet;
+use ignore::{WalkBuilder, WalkState};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+
+pub const TSH_IGNORE_FILENAME: &str = ".tshignore";
+
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    pub min_depth: usize,
+    pub max_depth: Option<usize>,
+    pub threads: usize,
+    pub exclude: PatternSet,
+    pub include: PatternSet,
+    pub project_markers: Vec<String>,
+    pub ignore_files: bool,
+}
+
+impl WalkOptions {
+    pub fn prunes(&self, path: &Path) -> bool {
+        self.exclude.matches(path)
+    }
+
+    pub fn includes(&self, path: &Path) -> bool {
+        self.include.is_empty() || self.include.matches(path)
+    }
+
+    pub fn projects_only(&self) -> bool {
+        !self.project_markers.is_empty()
+    }
+
+    pub fn is_project(&self, dir: &Path) -> bool {
+        self.project_markers
+            .iter()
+            .any(|marker| dir.join(marker).symlink_metadata().is_ok())
+    }
+
+    pub fn is_candidate(&self, dir: &Path, depth: usize) -> bool {
+        depth >= self.min_depth
+            && self.includes(dir)
+            && (!self.projects_only() || self.is_project(dir))
+    }
+
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "min={} max={} exclude={} include={} markers={} ignore_files={}",
+            self.min_depth,
+            self.max_depth
+                .map_or_else(|| "-".to_string(), |d| d.to_string()),
+            self.exclude.describe(),
+            self.include.describe(),
+            self.project_markers.join(","),
+            self.ignore_files
+        )
+    }
+}
+
+pub fn walk_tree(roots: &[PathBuf], options: &WalkOptions) -> Vec<PathBuf> {
+    let (tx, rx) = mpsc::channel();
+    stream_tree(roots, options, tx);
+    rx.into_iter().collect()
+}
+
+pub fn list_children(dir: &Path, options: &WalkOptions) -> Vec<PathBuf> {
+    let mut builder = builder(dir, &[], options);
+    builder.max_depth(Some(1));
+    builder
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.depth() == 1)
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+fn builder(first: &Path, rest: &[PathBuf], options: &WalkOptions) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(first);
+    for root in rest {
+        builder.add(root);
+    }
+
+    let filter = options.clone();
+    builder
+        .standard_filters(false)
+        .git_ignore(options.ignore_files)
+        .git_exclude(options.ignore_files)
+        .ignore(options.ignore_files)
+        .parents(options.ignore_files)
+        .follow_links(false)
+        .max_depth(options.max_depth)
+        .threads(options.threads)
+        .filter_entry(move |entry| {
+            entry.file_type().is_some_and(|t| t.is_dir()) && !filter.prunes(entry.path())
+        });
+    if options.ignore_files {
+        builder.add_custom_ignore_filename(TSH_IGNORE_FILENAME);
+    }
+
+    builder
+}
+
+// Sends every directory that was traversed; which of them are candidates is up
+// to the caller. Projects are sent but not descended into.
+pub fn stream_tree(roots: &[PathBuf], options: &WalkOptions, tx: Sender<PathBuf>) {
+    let Some((first, rest)) = roots.split_first() else {
+        return;
+    };
+
+    let builder = builder(first, rest, options);
+
+    builder.build_parallel().run(|| {
+        let tx = tx.clone();
+        Box::new(move |entry| {
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
+            if !entry.file_type().is_some_and(|t| t.is_dir()) {
+                return WalkState::Continue;
+            }
+
+            let project = options.projects_only() && options.is_project(entry.path());
+            let next = if project && entry.depth() > 0 {
+                WalkState::Skip
+            } else {
+                WalkState::Continue
+            };
+
+            if tx.send(entry.into_path()).is_err() {
+                return WalkState::Quit;
+            }
+            next
+        })
+    });
+}